@@ -2,23 +2,36 @@
 // Copyright (C) 2024 agaeki
 
 use crate::options;
-use crate::options::VoiceRate;
+use crate::options::MAX_RATE;
+use crate::options::MIN_RATE;
+use crate::options::NORMAL_PITCH;
+use crate::options::NORMAL_RATE;
+use crate::selection;
+use iced::futures::channel::oneshot;
 use image::imageops;
 use image::ImageBuffer;
 use image::SubImage;
 use mouse_position::mouse_position::Mouse;
+use ocrs::ImageSource;
 use ocrs::OcrEngine;
 use ocrs::OcrEngineParams;
 use rten::Model;
+use rten_imageproc::Point;
+use rten_imageproc::Polygon;
 use serde::*;
 use std::cmp;
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::fs;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use tts::Features;
 use tts::Tts;
+use tts::Voice;
 use xcap::Monitor;
 
-#[derive(Clone, Debug, Copy, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Copy, Default, Serialize, Deserialize, PartialEq)]
 pub struct ScreenPoint {
     pub x: i32,
     pub y: i32,
@@ -66,15 +79,19 @@ pub struct ImagePoint {
     pub y: u32,
 }
 
-pub fn init_engine(settings: &options::Settings) -> OcrEngine {
+pub fn init_engine(settings: &options::Settings) -> Result<OcrEngine, String> {
     println!("Opening detection data");
     // Use the `download-models.sh` script to download the models.
 
-    let detection_model_data = fs::read(&settings.detection_file).unwrap();
-    let rec_model_data = fs::read(&settings.recognition_file).unwrap();
+    let detection_model_data = fs::read(&settings.detection_file)
+        .map_err(|e| format!("{:?}: {e}", settings.detection_file))?;
+    let rec_model_data = fs::read(&settings.recognition_file)
+        .map_err(|e| format!("{:?}: {e}", settings.recognition_file))?;
 
-    let detection_model = Model::load(detection_model_data).unwrap();
-    let recognition_model = Model::load(rec_model_data).unwrap();
+    let detection_model = Model::load(detection_model_data)
+        .map_err(|e| format!("{:?}: {e}", settings.detection_file))?;
+    let recognition_model =
+        Model::load(rec_model_data).map_err(|e| format!("{:?}: {e}", settings.recognition_file))?;
 
     println!("Initialising OCR engine");
 
@@ -83,12 +100,39 @@ pub fn init_engine(settings: &options::Settings) -> OcrEngine {
         recognition_model: Some(recognition_model),
         ..Default::default()
     })
-    .unwrap()
+    .map_err(|e| e.to_string())
+}
+
+/// Counts the utterances a backend has said it started and finished speaking, for backends with utterance callbacks
+#[derive(Debug, Default)]
+pub struct Utterances {
+    started: AtomicUsize,
+    finished: AtomicUsize,
 }
 
-pub fn init_tts(settings: &options::Settings) -> Tts {
+impl Utterances {
+    /// Whether the backend is part way through an utterance. Counting rather than tracking ids copes with the
+    /// end of an interrupted utterance being reported after the start of the next
+    pub fn in_progress(&self) -> bool {
+        self.started.load(Ordering::SeqCst) > self.finished.load(Ordering::SeqCst)
+    }
+
+    fn start(&self) {
+        self.started.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn finish(&self) {
+        self.finished.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Starts a Text-to-Speech backend set up with `settings`, which can fail if the platform has none,
+/// won't start another one or rejects the settings. Its utterance callbacks, if it has them, are counted in `utterances`
+pub fn init_tts(settings: &options::Settings, utterances: &Arc<Utterances>) -> Result<Tts, String> {
     println!("Initialising reader");
-    let mut inner_tts = Tts::default().expect("Failed to start Text-to-Speech");
+    let mut inner_tts =
+        Tts::default().map_err(|e| format!("Failed to start Text-to-Speech: {e}"))?;
+    let set_up_error = |e: tts::Error| format!("Failed to set up Text-to-Speech: {e}");
     if Tts::screen_reader_available() {
         println!("A screen reader is available on this platform.");
     } else {
@@ -99,59 +143,141 @@ pub fn init_tts(settings: &options::Settings) -> Tts {
         ..
     } = inner_tts.supported_features();
     if utterance_callbacks {
+        let started = utterances.clone();
         inner_tts
-            .on_utterance_begin(Some(Box::new(|utterance| {
-                println!("Started speaking {:?}", utterance)
+            .on_utterance_begin(Some(Box::new(move |utterance| {
+                println!("Started speaking {:?}", utterance);
+                started.start();
             })))
-            .unwrap();
+            .map_err(set_up_error)?;
+        let finished = utterances.clone();
         inner_tts
-            .on_utterance_end(Some(Box::new(|utterance| {
-                println!("Finished speaking {:?}", utterance)
+            .on_utterance_end(Some(Box::new(move |utterance| {
+                println!("Finished speaking {:?}", utterance);
+                finished.finish();
             })))
-            .unwrap();
+            .map_err(set_up_error)?;
+        let stopped = utterances.clone();
         inner_tts
-            .on_utterance_stop(Some(Box::new(|utterance| {
-                println!("Stopped speaking {:?}", utterance)
+            .on_utterance_stop(Some(Box::new(move |utterance| {
+                println!("Stopped speaking {:?}", utterance);
+                stopped.finish();
             })))
-            .unwrap();
+            .map_err(set_up_error)?;
     }
+    configure_tts(&mut inner_tts, settings).map_err(set_up_error)?;
+    Ok(inner_tts)
+}
 
-    let pitch_coefficient = (inner_tts.max_pitch() - inner_tts.min_pitch()) / 6.;
-    let pitch_multiplier: f32 = settings.pitch.into();
-    inner_tts
-        .set_pitch(inner_tts.min_pitch() + pitch_multiplier * pitch_coefficient)
-        .unwrap();
+/// Sets the pitch, rate, volume and voice of a running backend from `settings`. Anything the backend
+/// doesn't support is left as it is, as is the voice when none are installed
+pub fn configure_tts(inner_tts: &mut Tts, settings: &options::Settings) -> Result<(), tts::Error> {
+    let features = inner_tts.supported_features();
+    if features.pitch {
+        let pitch = scale_to_backend(
+            settings.pitch,
+            (0., NORMAL_PITCH, 1.),
+            (
+                inner_tts.min_pitch(),
+                inner_tts.normal_pitch(),
+                inner_tts.max_pitch(),
+            ),
+        );
+        inner_tts.set_pitch(pitch)?;
+    }
 
-    if settings.rate < VoiceRate::Default {
-        let rate_coefficient = (inner_tts.normal_rate() - inner_tts.min_rate()) / 2.;
-        let rate_multiplier: f32 = Into::<f32>::into(settings.rate) - 1.;
+    if features.rate {
+        let rate = scale_to_backend(
+            settings.rate as f32,
+            (MIN_RATE as f32, NORMAL_RATE as f32, MAX_RATE as f32),
+            (
+                inner_tts.min_rate(),
+                inner_tts.normal_rate(),
+                inner_tts.max_rate(),
+            ),
+        );
+        inner_tts.set_rate(rate)?;
+    }
 
-        let rate = inner_tts.min_rate() + (rate_coefficient * rate_multiplier);
-        inner_tts.set_rate(rate).unwrap();
-    } else if settings.rate > VoiceRate::Default {
-        let rate_coefficient = (inner_tts.max_rate() - inner_tts.normal_rate()) / 3.;
-        let rate_multiplier: f32 =
-            Into::<f32>::into(settings.rate) - Into::<f32>::into(VoiceRate::Default) - 1.;
+    if features.volume {
+        let volume_coefficient = (inner_tts.max_volume() - inner_tts.min_volume()) / 255.;
+        inner_tts.set_volume(settings.volume as f32 * volume_coefficient)?;
+    }
 
-        let rate = inner_tts.normal_rate() + (rate_coefficient * rate_multiplier);
-        inner_tts.set_rate(rate).unwrap();
+    if features.voice {
+        let mut voices = inner_tts.voices()?;
+        let chosen_voice = match voices.iter().position(|v| v.id() == settings.voice) {
+            Some(index) => Some(voices.swap_remove(index)),
+            None => voices.into_iter().next(),
+        };
+        if let Some(chosen_voice) = chosen_voice {
+            println!("Setting voice to {:?}", &chosen_voice);
+            inner_tts.set_voice(&chosen_voice)?;
+        }
     }
+    Ok(())
+}
 
-    let volume_coefficient = (inner_tts.max_volume() - inner_tts.min_volume()) / 255.;
-    inner_tts
-        .set_volume(settings.volume as f32 * volume_coefficient)
-        .unwrap();
+/// Finds an installed voice for `language` (e.g. "en" or "pt-BR"), in the order [`choose_voice`] prefers them
+pub fn voice_for_language(
+    tts: &Tts,
+    language: &str,
+    settings: &options::Settings,
+) -> Option<Voice> {
+    let voices = tts.voices().ok()?;
+    let tags = voices
+        .iter()
+        .map(|v| (v.id(), v.language().as_str().to_string()))
+        .collect::<Vec<_>>();
+    choose_voice(&tags, language, &settings.voice, &settings.language_voices)
+        .map(|index| voices[index].clone())
+}
 
-    let chosen_voice = inner_tts
-        .voices()
-        .unwrap()
-        .into_iter()
-        .find(|v| v.id() == settings.voice)
-        .unwrap_or(inner_tts.voices().unwrap()[0].clone());
-    println!("Setting voice to {:?}", &chosen_voice);
-    inner_tts.set_voice(&chosen_voice).unwrap();
+/// Picks from `voices`, given as `(id, language tag)`, the voice chosen for `language` in `language_voices`,
+/// then `preferred` if it speaks the language, then a voice tagged with exactly `language`, then any voice
+/// for its main language, so "en-GB" voices can read "en" text. Returns the index of the voice picked
+fn choose_voice(
+    voices: &[(String, String)],
+    language: &str,
+    preferred: &str,
+    language_voices: &BTreeMap<String, String>,
+) -> Option<usize> {
+    let primary = |tag: &str| {
+        tag.split(['-', '_'])
+            .next()
+            .unwrap_or(tag)
+            .to_ascii_lowercase()
+    };
+    let speaks_language = |tag: &str| primary(tag) == primary(language);
+    let exact = language.replace('_', "-");
+    language_voices
+        .get(language)
+        .and_then(|chosen| voices.iter().position(|(id, _)| id == chosen))
+        .or_else(|| {
+            voices
+                .iter()
+                .position(|(id, tag)| id == preferred && speaks_language(tag))
+        })
+        .or_else(|| {
+            voices
+                .iter()
+                .position(|(_, tag)| tag.replace('_', "-").eq_ignore_ascii_case(&exact))
+        })
+        .or_else(|| voices.iter().position(|(_, tag)| speaks_language(tag)))
+}
 
-    inner_tts
+/// Maps `value` from `(low, mid, high)` onto a backend's `(min, normal, max)` range.
+/// Backends don't put their normal value in the middle of their range, so each half is scaled separately
+pub fn scale_to_backend(
+    value: f32,
+    (low, mid, high): (f32, f32, f32),
+    (min, normal, max): (f32, f32, f32),
+) -> f32 {
+    if value < mid {
+        min + (value - low) / (mid - low) * (normal - min)
+    } else {
+        normal + (value - mid) / (high - mid) * (max - normal)
+    }
 }
 
 pub fn get_cropped_image_source<'a>(
@@ -174,11 +300,190 @@ pub fn get_cropped_image_source<'a>(
     let cropped_buf: SubImage<&ImageBuffer<image::Rgba<u8>, Vec<u8>>> =
         imageops::crop_imm(&raw_img, rect_start.x, rect_start.y, new_width, new_height);
 
-    cropped_buf.to_image().save("cropped_buf.png").unwrap();
-
     (cropped_buf.to_image().into_raw(), new_width, new_height)
 }
 
+/// Crops the screenshot to the bounding box of `polygon` and whites out everything outside it.
+/// Also returns the polygon moved into the cropped image's coordinates
+pub fn get_masked_image_source(
+    screenshot: Vec<u8>,
+    screenshot_size: (u32, u32),
+    polygon: &[ScreenPoint],
+) -> (Vec<u8>, u32, u32, Polygon) {
+    let (top_left, bottom_right) = selection::bounding_box(polygon).unwrap_or_default();
+    let (mut bytes, width, height) =
+        get_cropped_image_source(screenshot, screenshot_size, top_left, bottom_right);
+
+    let origin = get_image_coords(top_left, screenshot_size);
+    let shape = Polygon::new(
+        polygon
+            .iter()
+            .map(|p| {
+                let p = get_image_coords(*p, screenshot_size);
+                Point::from_yx(p.y as i32 - origin.y as i32, p.x as i32 - origin.x as i32)
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    let mut inside = vec![false; (width * height) as usize];
+    for p in shape.fill_iter() {
+        if (0..width as i32).contains(&p.x) && (0..height as i32).contains(&p.y) {
+            inside[(p.y as u32 * width + p.x as u32) as usize] = true;
+        }
+    }
+    for (pixel, inside) in bytes.chunks_exact_mut(4).zip(inside) {
+        if !inside {
+            pixel.copy_from_slice(&[255, 255, 255, 255]);
+        }
+    }
+
+    (bytes, width, height, shape)
+}
+
+/// Runs [`get_cropped_image_source`] and [`recognise_image`] on their own thread, so the selection
+/// can keep being adjusted while the text in it is recognised
+pub async fn recognise_rect_in_background(
+    engine: Arc<OcrEngine>,
+    screenshot: Arc<Vec<u8>>,
+    screenshot_size: (u32, u32),
+    first_corner: ScreenPoint,
+    second_corner: ScreenPoint,
+) -> Vec<String> {
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let (bytes, width, height) = get_cropped_image_source(
+            screenshot.to_vec(),
+            screenshot_size,
+            first_corner,
+            second_corner,
+        );
+        let _ = sender.send(recognise_image(&engine, &bytes, (width, height), None));
+    });
+    receiver.await.unwrap_or_default()
+}
+
+/// Recognises the lines of text in an image, keeping only words entirely inside `within` if given
+pub fn recognise_image(
+    engine: &OcrEngine,
+    img_source_bytes: &[u8],
+    size: (u32, u32),
+    within: Option<&Polygon>,
+) -> Vec<String> {
+    let img_source = ImageSource::from_bytes(img_source_bytes, size).unwrap();
+
+    let ocr_input = engine.prepare_input(img_source).unwrap();
+
+    // Get oriented bounding boxes of text words in input image.
+    let mut word_rects = engine.detect_words(&ocr_input).unwrap();
+    if let Some(polygon) = within {
+        word_rects.retain(|word| {
+            word.corners().iter().all(|corner| {
+                polygon.contains_pixel(rten_imageproc::Point::from_yx(
+                    corner.y.round() as i32,
+                    corner.x.round() as i32,
+                ))
+            })
+        });
+    }
+
+    // Group words into lines. Each line is represented by a list of word
+    // bounding boxes.
+    let line_rects = engine.find_text_lines(&ocr_input, &word_rects);
+
+    engine
+        .recognize_text(&ocr_input, &line_rects[..])
+        .unwrap()
+        .into_iter()
+        .map(|x| x.map_or("".to_string(), |x| x.to_string()))
+        .collect()
+}
+
+/// The text found in a whole screenshot, as boxes in reading order
+#[derive(Debug, Clone, Default)]
+pub struct TextBoxes {
+    pub lines: Vec<(ImagePoint, ImagePoint)>,
+    pub words: Vec<(ImagePoint, ImagePoint)>,
+}
+
+/// Runs [`detect_text_boxes`] on its own thread, as searching a whole screenshot takes too long to do between frames
+pub async fn detect_text_boxes_in_background(
+    engine: Arc<OcrEngine>,
+    screenshot: Arc<Vec<u8>>,
+    buffer_size: (u32, u32),
+    screenshot_size: (u32, u32),
+) -> TextBoxes {
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(detect_text_boxes(
+            &engine,
+            &screenshot,
+            buffer_size,
+            screenshot_size,
+        ));
+    });
+    receiver.await.unwrap_or_default()
+}
+
+/// Finds the bounding box of each line and each word of text in a whole screenshot
+pub fn detect_text_boxes(
+    engine: &OcrEngine,
+    screenshot: &[u8],
+    buffer_size: (u32, u32),
+    screenshot_size: (u32, u32),
+) -> TextBoxes {
+    let Ok(img_source) = ImageSource::from_bytes(screenshot, buffer_size) else {
+        eprintln!("Screenshot doesn't match its size {:?}", buffer_size);
+        return TextBoxes::default();
+    };
+    let Ok(ocr_input) = engine.prepare_input(img_source) else {
+        return TextBoxes::default();
+    };
+    let word_rects = engine.detect_words(&ocr_input).unwrap_or_default();
+
+    let lines = engine.find_text_lines(&ocr_input, &word_rects);
+    let scale = |rect: rten_imageproc::Rect<f32>| {
+        scale_box(
+            (rect.left(), rect.top(), rect.right(), rect.bottom()),
+            buffer_size,
+            screenshot_size,
+        )
+    };
+    TextBoxes {
+        lines: lines
+            .iter()
+            .filter_map(|line| rten_imageproc::bounding_rect(line.iter()))
+            .map(scale)
+            .collect(),
+        words: lines
+            .iter()
+            .flatten()
+            .filter_map(|word| rten_imageproc::bounding_rect(std::iter::once(word)))
+            .map(scale)
+            .collect(),
+    }
+}
+
+/// Scales a box found in the screenshot's pixels, given as `(left, top, right, bottom)`, to the
+/// scaled down `screenshot_size` the rest of the overlay works in on high DPI screens
+fn scale_box(
+    (left, top, right, bottom): (f32, f32, f32, f32),
+    buffer_size: (u32, u32),
+    screenshot_size: (u32, u32),
+) -> (ImagePoint, ImagePoint) {
+    let scale_x = screenshot_size.0 as f32 / buffer_size.0.max(1) as f32;
+    let scale_y = screenshot_size.1 as f32 / buffer_size.1.max(1) as f32;
+    (
+        ImagePoint {
+            x: (left * scale_x).max(0.) as u32,
+            y: (top * scale_y).max(0.) as u32,
+        },
+        ImagePoint {
+            x: ((right * scale_x).ceil().max(0.) as u32).min(screenshot_size.0.saturating_sub(1)),
+            y: ((bottom * scale_y).ceil().max(0.) as u32).min(screenshot_size.1.saturating_sub(1)),
+        },
+    )
+}
+
 pub fn get_top_left(point1: ImagePoint, point2: ImagePoint) -> ImagePoint {
     ImagePoint {
         x: cmp::min(point1.x, point2.x),
@@ -202,35 +507,6 @@ pub fn get_image_coords(point: ScreenPoint, image_size: (u32, u32)) -> ImagePoin
     }
 }
 
-pub fn draw_rectangle(
-    buffer: &mut [u8],
-    size: (u32, u32),
-    start: ImagePoint,
-    end: ImagePoint,
-    colour: &[u8; 4],
-) {
-    // Work out the indices of the edges of the rectangle in the 1D vec of image data, *4 because a pixel is RGBA
-    for i in start.x..end.x {
-        // for j_min
-        let left_index = (((size.0 * start.y) + i) * 4) as usize;
-        // for j_max
-        let right_index = (((size.0 * end.y) + i) * 4) as usize;
-
-        buffer[left_index..left_index + 4].clone_from_slice(colour);
-        buffer[right_index..right_index + 4].clone_from_slice(colour);
-    }
-
-    for j in start.y..end.y {
-        // for i_min
-        let top_index = (((size.0 * j) + start.x) * 4) as usize;
-        // for i_max
-        let bottom_index = (((size.0 * j) + end.x) * 4) as usize;
-
-        buffer[top_index..top_index + 4].clone_from_slice(colour);
-        buffer[bottom_index..bottom_index + 4].clone_from_slice(colour);
-    }
-}
-
 pub fn get_mouse_position() -> Option<ScreenPoint> {
     match Mouse::get_mouse_position() {
         Mouse::Position { x, y } => {
@@ -247,3 +523,172 @@ pub fn get_mouse_position() -> Option<ScreenPoint> {
         }
     }
 }
+
+/// Keeps a window of `size` at `position` inside the monitor it's mostly on, so it can't be lost off screen,
+/// and snaps it flush against that monitor's edges when it's within `snap_distance` of them
+pub fn dock_window(position: ScreenPoint, size: (i32, i32), snap_distance: i32) -> ScreenPoint {
+    dock_to_monitors(position, size, snap_distance, &monitor_bounds())
+}
+
+/// Each monitor's `(left, top, right, bottom)`, in the same scaled down coordinates as the mouse and window positions
+pub fn monitor_bounds() -> Vec<(i32, i32, i32, i32)> {
+    Monitor::all()
+        .unwrap_or_default()
+        .iter()
+        .map(|m| {
+            let scale = m.scale_factor();
+            let left = (m.x() as f32 / scale).round() as i32;
+            let top = (m.y() as f32 / scale).round() as i32;
+            let right = left + (m.width() as f32 / scale).round() as i32;
+            let bottom = top + (m.height() as f32 / scale).round() as i32;
+            (left, top, right, bottom)
+        })
+        .collect()
+}
+
+/// [`dock_window`] for monitors given as `(left, top, right, bottom)` bounds
+fn dock_to_monitors(
+    position: ScreenPoint,
+    size: (i32, i32),
+    snap_distance: i32,
+    monitors: &[(i32, i32, i32, i32)],
+) -> ScreenPoint {
+    let centre = (position.x + size.0 / 2, position.y + size.1 / 2);
+    let bounds = monitors.iter().min_by_key(|&&(left, top, right, bottom)| {
+        let dx = (left - centre.0).max(centre.0 - right).max(0);
+        let dy = (top - centre.1).max(centre.1 - bottom).max(0);
+        dx * dx + dy * dy
+    });
+    let Some(&(left, top, right, bottom)) = bounds else {
+        return position;
+    };
+
+    let dock = |value: i32, length: i32, start: i32, end: i32| {
+        let value = value.min(end - length).max(start);
+        if value - start <= snap_distance {
+            start
+        } else if end - (value + length) <= snap_distance {
+            end - length
+        } else {
+            value
+        }
+    };
+    ScreenPoint {
+        x: dock(position.x, size.0, left, right),
+        y: dock(position.y, size.1, top, bottom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voices() -> Vec<(String, String)> {
+        [
+            ("david", "en-US"),
+            ("hazel", "en-GB"),
+            ("helena", "es-ES"),
+            ("maria", "pt-PT"),
+            ("daniel", "pt-BR"),
+        ]
+        .iter()
+        .map(|(id, tag)| (id.to_string(), tag.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn voice_chosen_for_the_language_comes_first() {
+        let chosen = BTreeMap::from([("en".to_string(), "hazel".to_string())]);
+        assert_eq!(choose_voice(&voices(), "en", "david", &chosen), Some(1));
+        // A chosen voice that isn't installed is passed over
+        let missing = BTreeMap::from([("en".to_string(), "zira".to_string())]);
+        assert_eq!(choose_voice(&voices(), "en", "", &missing), Some(0));
+    }
+
+    #[test]
+    fn preferred_voice_is_used_when_it_speaks_the_language() {
+        let none = BTreeMap::new();
+        assert_eq!(choose_voice(&voices(), "en", "hazel", &none), Some(1));
+        assert_eq!(choose_voice(&voices(), "es", "hazel", &none), Some(2));
+    }
+
+    #[test]
+    fn exact_tag_comes_before_the_main_language() {
+        let none = BTreeMap::new();
+        assert_eq!(choose_voice(&voices(), "pt-BR", "", &none), Some(4));
+        assert_eq!(choose_voice(&voices(), "PT_br", "", &none), Some(4));
+        assert_eq!(choose_voice(&voices(), "pt", "", &none), Some(3));
+        assert_eq!(choose_voice(&voices(), "en-AU", "", &none), Some(0));
+        assert_eq!(choose_voice(&voices(), "fr", "", &none), None);
+    }
+
+    #[test]
+    fn utterance_interrupted_by_the_next_is_still_in_progress() {
+        let utterances = Utterances::default();
+        assert!(!utterances.in_progress());
+        utterances.start();
+        // The next sentence interrupts, but the first one's stop is only reported after it starts
+        utterances.start();
+        utterances.finish();
+        assert!(utterances.in_progress());
+        utterances.finish();
+        assert!(!utterances.in_progress());
+    }
+
+    #[test]
+    fn scales_text_boxes_down_for_high_dpi() {
+        let (top_left, bottom_right) =
+            scale_box((100., 50., 301., 81.), (3840, 2160), (1920, 1080));
+        assert_eq!((top_left.x, top_left.y), (50, 25));
+        assert_eq!((bottom_right.x, bottom_right.y), (151, 41));
+    }
+
+    #[test]
+    fn keeps_text_boxes_inside_the_screenshot() {
+        let (top_left, bottom_right) =
+            scale_box((-4., -2., 2000., 1100.), (1920, 1080), (1920, 1080));
+        assert_eq!((top_left.x, top_left.y), (0, 0));
+        assert_eq!((bottom_right.x, bottom_right.y), (1919, 1079));
+    }
+
+    const SIZE: (i32, i32) = (200, 50);
+    const SNAP: i32 = 16;
+    /// Two 1920x1080 monitors side by side
+    const MONITORS: [(i32, i32, i32, i32); 2] = [(0, 0, 1920, 1080), (1920, 0, 3840, 1080)];
+
+    fn dock(x: i32, y: i32) -> ScreenPoint {
+        dock_to_monitors(ScreenPoint { x, y }, SIZE, SNAP, &MONITORS)
+    }
+
+    #[test]
+    fn leaves_window_away_from_edges() {
+        assert_eq!(dock(500, 400), ScreenPoint { x: 500, y: 400 });
+    }
+
+    #[test]
+    fn snaps_to_nearby_edges() {
+        assert_eq!(dock(10, 1080 - 50 - 12), ScreenPoint { x: 0, y: 1030 });
+        assert_eq!(dock(1920 - 200 - 16, 16), ScreenPoint { x: 1720, y: 0 });
+        // Just out of reach
+        assert_eq!(dock(17, 17), ScreenPoint { x: 17, y: 17 });
+    }
+
+    #[test]
+    fn pulls_window_back_on_screen() {
+        assert_eq!(dock(-150, -500), ScreenPoint { x: 0, y: 0 });
+        assert_eq!(dock(5000, 2000), ScreenPoint { x: 3640, y: 1030 });
+    }
+
+    #[test]
+    fn stays_on_the_monitor_its_centre_is_on() {
+        // Mostly on the right hand monitor, so it snaps to that monitor's left edge rather than the left one's right
+        assert_eq!(dock(1850, 400), ScreenPoint { x: 1920, y: 400 });
+        assert_eq!(dock(1710, 400), ScreenPoint { x: 1720, y: 400 });
+    }
+
+    #[test]
+    fn no_monitors_leaves_window_alone() {
+        let position = ScreenPoint { x: -5000, y: 5 };
+        assert_eq!(dock_to_monitors(position, SIZE, SNAP, &[]), position);
+    }
+}
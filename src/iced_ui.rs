@@ -2,17 +2,38 @@
 // Copyright (C) 2024 agaeki
 
 use crate::iced_logic;
-use crate::iced_logic::draw_rectangle;
 use crate::iced_logic::get_bottom_right;
 use crate::iced_logic::get_top_left;
+use crate::iced_logic::ImagePoint;
 use crate::iced_logic::ScreenPoint;
-use crate::options;
+use crate::iced_logic::TextBoxes;
+use crate::iced_logic::Utterances;
+use crate::language;
+use crate::lexicon::Lexicon;
+use crate::lexicon::LexiconEntry;
+use crate::magnifier;
+use crate::magnifier::Lens;
+use crate::options::ReaderColours;
+use crate::options::ReadingMode;
+use crate::options::SelectionShape;
 use crate::options::Settings;
-use crate::options::VoicePitch;
-use crate::options::VoiceRate;
+use crate::options::MAX_RATE;
+use crate::options::MIN_RATE;
+use crate::overlay::Overlay;
+use crate::overlay::Shape;
+use crate::overrides;
+use crate::selection;
+use crate::selection::Selection;
+use crate::settings_state::SettingsState;
+use crate::speech;
+use crate::speech::SpeechQueue;
+use crate::translate;
 use iced::alignment::Horizontal;
 use iced::event;
 use iced::executor;
+use iced::keyboard;
+use iced::keyboard::key::Named;
+use iced::keyboard::Key;
 use iced::widget;
 use iced::widget::button;
 use iced::widget::column;
@@ -22,42 +43,130 @@ use iced::widget::image::Handle;
 use iced::widget::mouse_area;
 use iced::widget::row;
 use iced::widget::text;
+use iced::widget::tooltip;
 use iced::widget::vertical_rule;
 use iced::window::Id;
 use iced::Application;
 use iced::Color;
 use iced::Command;
-use iced::ContentFit;
 use iced::Element;
 use iced::Point;
 use iced::Size;
 use iced::Subscription;
 use iced::Theme;
-use ocrs::ImageSource;
 use ocrs::OcrEngine;
 use rfd::FileDialog;
+use rten_imageproc::Polygon;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 use tts::Tts;
 use xcap::Monitor;
 
-pub const WINDOW_SIZE: Size = Size::new(108., 31.);
-pub const WINDOW_SIZE_SETTINGS: Size = Size::new(200., 400.);
+pub const WINDOW_SIZE: Size = Size::new(180., 31.);
+pub const WINDOW_SIZE_SPEAKING: Size = Size::new(365., 31.);
+pub const WINDOW_SIZE_SETTINGS: Size = Size::new(200., 810.);
+// Width of the grip at the start of the bar for dragging the window
+const GRIP_WIDTH: f32 = 20.;
+// How close to a screen edge the window has to be dropped to snap against it
+const SNAP_DISTANCE: i32 = 16;
+// Extra room in the settings for each language with its own voice
+const LANGUAGE_VOICE_ROW_HEIGHT: f32 = 30.;
+// Extra room on the bar for the profile button, when there are profiles to switch between
+const PROFILE_BUTTON_WIDTH: f32 = 30.;
+// Extra room on the idle bar for the reader panel's button, once something has been read
+const READER_BUTTON_WIDTH: f32 = 55.;
+// Smallest the reader panel can be dragged to
+const MIN_READER_SIZE: (u16, u16) = (200, 100);
+// Space around the reader panel's text
+const READER_PADDING: f32 = 10.;
+
+// How often to check whether the current sentence has finished
+const SPEECH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Backends can take a moment to report is_speaking after speak() returns
+const UTTERANCE_GRACE: Duration = Duration::from_millis(300);
+// How far the arrow keys move or resize the selection, holding alt moves a single pixel
+const KEYBOARD_STEP: i32 = 10;
+// Size of the selection the arrow keys start with, centred on the mouse
+const KEYBOARD_SELECTION_SIZE: (i32, i32) = (200, 40);
+// How long the mouse has to rest on a picked word or line to read it, with hover_to_read
+const HOVER_DWELL: Duration = Duration::from_millis(800);
+// Room left around a picked word or line when it is cropped for reading
+const TEXT_BOX_PADDING: i32 = 4;
+// How often the magnifier captures the screen around the mouse
+const MAGNIFIER_INTERVAL: Duration = Duration::from_millis(150);
+// Height of the magnifier's controls above the lens
+const MAGNIFIER_CONTROLS_HEIGHT: f32 = 31.;
+// How much the magnifier's + and - buttons change the zoom
+const MAGNIFIER_ZOOM_STEP: f32 = 0.5;
+// How long a selection has to stay still before the text in it is previewed
+const SELECTION_PREVIEW_DELAY: Duration = Duration::from_millis(300);
+// Read by the settings' Preview button
+const PREVIEW_PHRASE: &str = "The quick brown fox jumps over the lazy dog.";
+// How often to check settings.json for changes made outside the app
+const SETTINGS_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Clone)]
 pub enum Message {
     Read,
     Stop,
+    TogglePause,
+    NextLine,
+    PreviousLine,
+    NextSentence,
+    PreviousSentence,
+    Repeat,
+    SpeechTick,
+    HoverTick,
+    PreviewTick,
+    // Text recognised in the background for a selection in the screenshot with this number
+    SelectionPreviewed(usize, (ScreenPoint, ScreenPoint), Vec<String>),
     StartRect,
     EndRect,
     MouseMoved(ScreenPoint),
+    MoveSelection(i32, i32),
+    ResizeSelection(i32, i32),
+    SnapSelection(bool),
+    ConfirmSelection,
+    CancelSelection,
+    ModifiersChanged(keyboard::Modifiers),
+    Magnifier,
+    MagnifierTick,
+    MagnifierCaptured(ScreenPoint, Result<Lens, String>),
+    // Text was found in the screenshot with this number
+    TextBoxesDetected(usize, TextBoxes),
+    MagnifierZoom(f32),
+    ReadMagnifier,
+    Reader,
+    ReaderWord(usize),
+    SpeakReaderSelection,
+    // The translation with this number finished, with the lines sent to be translated
+    Translated(usize, Vec<String>, Result<Vec<String>, String>),
+    ResizeReader,
     Settings,
     SettingsCancel,
     SettingsApply,
+    SettingsUndo,
+    PreviewVoice,
     SettingChanged(Arc<dyn Fn(&mut Settings) + Send + Sync>),
     SettingError(String),
+    CheckSettingsFile,
+    SwitchProfile(usize),
+    NextProfile,
+    ProfileNameChanged(String),
+    LanguageVoiceNameChanged(String),
+    SaveProfile,
+    DeleteProfile,
+    Lexicon,
+    LexiconChanged(Arc<dyn Fn(&mut Lexicon) + Send + Sync>),
+    LexiconImport,
+    LexiconExport,
     DragWindow,
+    WindowMoved(ScreenPoint),
+    WindowFocusChanged,
     ReleaseWindow,
     Quit,
 }
@@ -69,20 +178,74 @@ impl Debug for Message {
 }
 
 pub struct IcedApp {
-    pub engine: OcrEngine,
+    // Shared with OCR running in the background
+    pub engine: Arc<OcrEngine>,
     pub tts: Tts,
-    pub screenshot_buffer: Vec<u8>,
+    // What the reading backend has reported speaking through its callbacks
+    pub utterances: Arc<Utterances>,
+    // Speaks the preview phrase with settings that haven't been applied yet
+    pub preview_tts: Option<Tts>,
+    pub screenshot_buffer: Arc<Vec<u8>>,
     pub screenshot_size: (u32, u32),
-    pub rect_start: Option<ScreenPoint>,
-    pub rect_end: Option<ScreenPoint>,
+    // Size of screenshot_buffer, which is in physical pixels and larger than screenshot_size on high DPI screens
+    pub screenshot_buffer_size: (u32, u32),
+    pub screenshot_origin: ScreenPoint,
+    // Counts screenshots taken, so text found in the background for an earlier one isn't shown over the next
+    pub capture: usize,
+    pub selection: Selection,
+    // Lines of text found in the screenshot, None until they're found in the background
+    pub text_lines: Option<Vec<(ImagePoint, ImagePoint)>>,
+    pub snapped_line: Option<usize>,
+    // Words or lines found up front when picking text rather than selecting it
+    pub text_boxes: Vec<(ImagePoint, ImagePoint)>,
+    pub hovered_box: Option<(usize, Instant)>,
+    // Text recognised in the selection while it is being adjusted
+    pub selection_preview: Option<String>,
+    // When the selection last changed, until its preview is recognised
+    pub preview_requested: Option<Instant>,
+    // Points of a lasso selection, in screen coordinates
+    pub lasso: Vec<ScreenPoint>,
+
+    // Made once per screenshot so the overlay doesn't copy the pixels again on every redraw
+    pub screenshot_handle: Option<Handle>,
 
-    pub screenshot_image: Option<Vec<u8>>,
+    pub magnifier_open: bool,
+    pub lens: Option<Lens>,
+    pub lens_view: Option<Handle>,
+    // A capture for the magnifier is running in the background
+    pub capturing_lens: bool,
+    // Where the window was moved to follow the mouse with the magnifier, None when it's where the user left it
+    pub magnifier_position: Option<ScreenPoint>,
+
+    pub speech: Option<SpeechQueue>,
+    // The last text read in full, shown in the reader panel
+    pub reader: Option<SpeechQueue>,
+    pub reader_open: bool,
+    // First and last word selected in the reader panel, counting words from the start of the text
+    pub reader_selection: Option<(usize, usize)>,
+    pub resizing_reader: bool,
+    pub utterance_started: Instant,
+    // The language the current voice was picked for, None when it's the chosen voice
+    pub voice_language: Option<String>,
+    // Counts translations started, so one that finishes after another capture or Stop isn't read
+    pub translation: usize,
+    pub modifiers: keyboard::Modifiers,
 
     pub settings_open: bool,
-    pub settings_dirty: bool,
+    // Pronunciations have been changed in the settings panel but not saved
+    pub lexicon_dirty: bool,
+    pub lexicon_open: bool,
+    pub profile_name: String,
+    // Language typed in to choose a voice for
+    pub language_voice_name: String,
     pub previous_drag_position: Option<ScreenPoint>,
+    // The window is being dragged by its grip and hasn't been dropped yet
+    pub dragging_window: bool,
 
-    pub settings: options::Settings,
+    pub settings: SettingsState,
+    pub settings_modified: Option<SystemTime>,
+    pub settings_error: Option<String>,
+    pub lexicon: Lexicon,
 }
 
 impl Application for IcedApp {
@@ -93,21 +256,46 @@ impl Application for IcedApp {
     type Flags = ();
 
     fn view(&self) -> Element<'_, Message> {
-        if let Some(screenshot_image) = &self.screenshot_image {
-            mouse_area(
-                iced::widget::image(Handle::from_pixels(
-                    self.screenshot_size.0,
-                    self.screenshot_size.1,
-                    screenshot_image.clone(),
-                ))
-                .content_fit(ContentFit::None),
-            )
+        if let Some(screenshot) = &self.screenshot_handle {
+            let overlay = mouse_area(Overlay::new(
+                screenshot.clone(),
+                self.screenshot_size,
+                &self.settings,
+                self.selection_shapes(),
+            ))
             .on_press(Message::StartRect)
             .on_release(Message::EndRect)
-            .into()
-        } else if let Ok(false) = self.tts.is_speaking() {
+            .on_middle_press(Message::ConfirmSelection)
+            .on_right_press(Message::CancelSelection);
+            match &self.selection_preview {
+                Some(preview) => tooltip(
+                    overlay,
+                    widget::container(column([
+                        text(if preview.is_empty() {
+                            "No text found"
+                        } else {
+                            preview
+                        })
+                        .into(),
+                        text("Enter or middle click to read, Escape to cancel")
+                            .size(12)
+                            .into(),
+                    ]))
+                    .max_width(400),
+                    tooltip::Position::FollowCursor,
+                )
+                .style(iced::theme::Container::Box)
+                .padding(5)
+                .into(),
+                None => overlay.into(),
+            }
+        } else if let Err(e) = self.tts.is_speaking() {
+            eprintln!("ERROR: {:?}", e);
+            text("ERROR").into()
+        } else if let Some(speech) = &self.speech {
             column([
                 row([
+                    grip(),
                     button(widget::image(Handle::from_memory(include_bytes!(
                         "quit_image.png"
                     ))))
@@ -118,15 +306,28 @@ impl Application for IcedApp {
                     ))))
                     .on_press(Message::Settings)
                     .into(),
-                    button("READ").on_press(Message::Read).into(),
+                    button("STOP").on_press(Message::Stop).into(),
+                    button(if speech.paused { ">" } else { "||" })
+                        .on_press(Message::TogglePause)
+                        .into(),
+                    button("|<").on_press(Message::PreviousLine).into(),
+                    button("<").on_press(Message::PreviousSentence).into(),
+                    button("R").on_press(Message::Repeat).into(),
+                    button(">").on_press(Message::NextSentence).into(),
+                    button(">|").on_press(Message::NextLine).into(),
+                    button("TEXT").on_press(Message::Reader).into(),
                 ])
+                .push_maybe(profile_button(self))
                 .into(),
+                magnifier_widget(self),
+                reader_widget(self),
                 settings_widget(&self),
             ])
             .into()
-        } else if let Ok(true) = self.tts.is_speaking() {
+        } else {
             column([
                 row([
+                    grip(),
                     button(widget::image(Handle::from_memory(include_bytes!(
                         "quit_image.png"
                     ))))
@@ -137,15 +338,21 @@ impl Application for IcedApp {
                     ))))
                     .on_press(Message::Settings)
                     .into(),
-                    button("STOP").on_press(Message::Stop).into(),
+                    button("READ").on_press(Message::Read).into(),
+                    button("ZOOM").on_press(Message::Magnifier).into(),
                 ])
+                .push_maybe(
+                    self.reader
+                        .as_ref()
+                        .map(|_| button("TEXT").on_press(Message::Reader)),
+                )
+                .push_maybe(profile_button(self))
                 .into(),
+                magnifier_widget(self),
+                reader_widget(self),
                 settings_widget(&self),
             ])
             .into()
-        } else {
-            eprintln!("ERROR: {:?}", self.tts.is_speaking());
-            text("ERROR").into()
         }
     }
 
@@ -168,137 +375,669 @@ impl Application for IcedApp {
                         ),
                     ]);
 
+                    self.screenshot_buffer_size = rgb_image.dimensions();
                     self.screenshot_size = (
                         (rgb_image.width() as f32 / monitor.scale_factor()).round() as u32,
                         (rgb_image.height() as f32 / monitor.scale_factor()).round() as u32,
                     );
+                    self.screenshot_origin = ScreenPoint {
+                        x: monitor.x(),
+                        y: monitor.y(),
+                    };
+                    self.text_lines = None;
+                    self.snapped_line = None;
+                    self.selection = Selection::Empty;
 
-                    // Store original screenshot so that we can draw a resizing rectangle on a clone without losing pixels
-                    self.screenshot_buffer = rgb_image.into_raw();
-
-                    self.screenshot_image = Some(self.screenshot_buffer.clone());
+                    // Keep the raw pixels for OCR, the overlay draws the selection over its own handle
+                    let pixels = rgb_image.into_raw();
+                    self.screenshot_handle = Some(Handle::from_pixels(
+                        self.screenshot_size.0,
+                        self.screenshot_size.1,
+                        pixels.clone(),
+                    ));
+                    self.screenshot_buffer = Arc::new(pixels);
+                    self.capture += 1;
 
-                    ret
+                    self.hovered_box = None;
+                    self.text_boxes.clear();
+                    // Searching the whole screen takes a while, show the overlay and outline the text or
+                    // let the selection snap to it once it's found
+                    let capture = self.capture;
+                    Command::batch([
+                        ret,
+                        Command::perform(
+                            iced_logic::detect_text_boxes_in_background(
+                                self.engine.clone(),
+                                self.screenshot_buffer.clone(),
+                                self.screenshot_buffer_size,
+                                self.screenshot_size,
+                            ),
+                            move |boxes| Message::TextBoxesDetected(capture, boxes),
+                        ),
+                    ])
                 } else {
                     Command::none()
                 }
             }
+            Message::TextBoxesDetected(capture, boxes) => {
+                if capture == self.capture && self.screenshot_handle.is_some() {
+                    self.text_boxes = match self.settings.selection_shape {
+                        SelectionShape::Words => boxes.words,
+                        SelectionShape::Lines => boxes.lines.clone(),
+                        SelectionShape::Rectangle | SelectionShape::Lasso => vec![],
+                    };
+                    self.text_lines = Some(boxes.lines);
+                    self.hovered_box = None;
+                }
+                Command::none()
+            }
             Message::StartRect => {
                 if let Some(ScreenPoint { x, y }) = iced_logic::get_mouse_position() {
+                    if self.settings.selection_shape == SelectionShape::Lasso {
+                        return self.lasso_click(self.clamp_to_screenshot(ScreenPoint { x, y }));
+                    }
+                    if self.settings.selection_shape.is_detected() {
+                        return match self.text_box_at(ScreenPoint { x, y }) {
+                            Some(index) => self.read_text_box(index),
+                            None => Command::none(),
+                        };
+                    }
+                    if !self.settings.drag_draw
+                        && let Selection::Dragging { .. } = self.selection
+                    {
+                        // The second click sets the opposite corner
+                        println!("End rect at {x} {y}");
+                        self.selection
+                            .drag_to(self.clamp_to_screenshot(ScreenPoint { x, y }));
+                        return self.finish_selection();
+                    }
                     println!("Start rect at {x} {y}");
-                    self.rect_start = Some(ScreenPoint { x: x, y: y });
+                    self.selection.press(ScreenPoint { x: x, y: y });
+                    if let Selection::Dragging { .. } = self.selection {
+                        self.selection_preview = None;
+                    }
                 }
                 Command::none()
             }
             Message::EndRect => {
-                if let Some(rect_start) = self.rect_start
-                    && let Some(rect_end) = self.rect_end
+                // Without drag_draw the selection keeps following the mouse until the next click
+                if !self.selection.is_dragging()
+                    || (!self.settings.drag_draw
+                        && matches!(self.selection, Selection::Dragging { .. }))
                 {
-                    self.speak_from_rect(
-                        self.screenshot_buffer.clone(),
-                        self.screenshot_size,
-                        rect_start.into(),
-                        rect_end.into(),
-                    );
+                    return Command::none();
                 }
-                self.rect_start = None;
-                self.screenshot_image = None;
-                Command::batch([
-                    iced::window::resize(Id::MAIN, WINDOW_SIZE),
-                    iced::window::move_to(Id::MAIN, self.settings.position.into()),
-                ])
+                if self.settings.selection_shape == SelectionShape::Lasso {
+                    return self.finish_lasso();
+                }
+                self.finish_selection()
             }
-            Message::MouseMoved(pos) => {
-                if let Some(rect_start) = self.rect_start
-                    && let Some(screenshot_image) = &mut self.screenshot_image
+            Message::ConfirmSelection => {
+                if self.settings.selection_shape == SelectionShape::Lasso
+                    && let Selection::Dragging { .. } = self.selection
                 {
-                    self.rect_end = Some(pos);
-                    screenshot_image.copy_from_slice(&self.screenshot_buffer[..]);
-                    let img_coord_start =
-                        iced_logic::get_image_coords(rect_start, self.screenshot_size);
-                    let img_coord_end = iced_logic::get_image_coords(pos, self.screenshot_size);
-
-                    draw_rectangle(
-                        &mut self.screenshot_image.as_mut().unwrap(),
-                        self.screenshot_size,
-                        get_top_left(img_coord_start, img_coord_end),
-                        get_bottom_right(img_coord_start, img_coord_end),
-                        &self.settings.rect_colour,
-                    );
+                    return self.finish_lasso();
+                }
+                match self.selection.confirm() {
+                    Some(corners) => self.read_selection(corners),
+                    None => Command::none(),
+                }
+            }
+            Message::CancelSelection => {
+                println!("Selection cancelled");
+                self.close_overlay()
+            }
+            Message::MoveSelection(dx, dy) => {
+                let (start, end) = self.keyboard_selection();
+                self.selection.select(
+                    self.clamp_to_screenshot(ScreenPoint {
+                        x: start.x + dx,
+                        y: start.y + dy,
+                    }),
+                    self.clamp_to_screenshot(ScreenPoint {
+                        x: end.x + dx,
+                        y: end.y + dy,
+                    }),
+                );
+                self.update_preview();
+                Command::none()
+            }
+            Message::ResizeSelection(dx, dy) => {
+                let (start, end) = self.keyboard_selection();
+                self.selection.select(
+                    start,
+                    self.clamp_to_screenshot(ScreenPoint {
+                        x: end.x + dx,
+                        y: end.y + dy,
+                    }),
+                );
+                self.update_preview();
+                Command::none()
+            }
+            Message::SnapSelection(forwards) => {
+                if !self.selection.is_open() {
                     return Command::none();
-                } else if let Some(previous_pos) = self.previous_drag_position {
-                    let x_diff = pos.x - previous_pos.x;
-                    let y_diff = pos.y - previous_pos.y;
-
-                    let new_position = ScreenPoint {
-                        x: self.settings.position.x + x_diff,
-                        y: self.settings.position.y + y_diff,
-                    };
-
-                    self.previous_drag_position = Some(pos.into());
-                    self.settings.position = new_position;
-                    return iced::window::move_to(Id::MAIN, self.settings.position.into());
                 }
+                // Nothing to snap to until the lines have been found in the background
+                let Some(text_lines) = &self.text_lines else {
+                    return Command::none();
+                };
+                if text_lines.is_empty() {
+                    return Command::none();
+                }
+                let line_count = text_lines.len();
+                let next_line = match self.snapped_line {
+                    Some(i) if forwards => (i + 1) % line_count,
+                    Some(i) => (i + line_count - 1) % line_count,
+                    None => 0,
+                };
+                let (top_left, bottom_right) = text_lines[next_line];
+                self.snapped_line = Some(next_line);
+                self.selection.select(
+                    self.to_screen_point(top_left),
+                    self.to_screen_point(bottom_right),
+                );
+                self.update_preview();
+                Command::none()
+            }
+            Message::MouseMoved(pos) => {
+                if self.settings.selection_shape.is_detected() && self.selection.is_open() {
+                    let hovered = self.text_box_at(pos);
+                    if hovered != self.hovered_box.map(|(index, _)| index) {
+                        self.hovered_box = hovered.map(|index| (index, Instant::now()));
+                    }
+                    return Command::none();
+                }
+                if self.selection.is_dragging() {
+                    let mut selection = self.selection;
+                    selection.drag_to(pos);
+                    let (min, max) = self.screenshot_bounds();
+                    selection.clamp(min, max);
+                    self.selection = selection;
+                    if self.settings.selection_shape == SelectionShape::Lasso
+                        && self.settings.drag_draw
+                        && let Some(last) = self.lasso.last()
+                        && ((last.x - pos.x).abs() >= selection::LASSO_POINT_SPACING
+                            || (last.y - pos.y).abs() >= selection::LASSO_POINT_SPACING)
+                    {
+                        self.lasso.push(self.clamp_to_screenshot(pos));
+                    }
+                    return Command::none();
+                } else if self.resizing_reader
+                    && let Some(previous_pos) = self.previous_drag_position
+                {
+                    self.previous_drag_position = Some(pos);
+                    let width = (self.settings.reader_width as i32 + pos.x - previous_pos.x)
+                        .max(MIN_READER_SIZE.0 as i32) as u16;
+                    let height = (self.settings.reader_height as i32 + pos.y - previous_pos.y)
+                        .max(MIN_READER_SIZE.1 as i32) as u16;
+                    self.settings.commit(|s| {
+                        s.reader_width = width;
+                        s.reader_height = height;
+                    });
+                    return iced::window::resize(Id::MAIN, self.window_size());
+                }
+                Command::none()
+            }
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
                 Command::none()
             }
             Message::Stop => {
                 if let Err(e) = self.tts.stop() {
                     eprintln!("Error stopping speaking: {:?}", e);
                 }
+                self.speech = None;
+                self.translation += 1;
+                iced::window::resize(Id::MAIN, self.window_size())
+            }
+            Message::TogglePause => {
+                if let Some(speech) = &mut self.speech {
+                    speech.paused = !speech.paused;
+                    if speech.paused {
+                        if let Err(e) = self.tts.stop() {
+                            eprintln!("Error pausing speaking: {:?}", e);
+                        }
+                    } else {
+                        self.speak_current();
+                    }
+                }
                 Command::none()
             }
-            Message::Settings => {
-                self.settings_open = !self.settings_open;
-                iced::window::resize(
-                    Id::MAIN,
-                    if self.settings_open {
-                        WINDOW_SIZE_SETTINGS
+            Message::NextLine => self.move_speech(|s| s.next_line()),
+            Message::PreviousLine => self.move_speech(|s| {
+                s.previous_line();
+                true
+            }),
+            Message::NextSentence => self.move_speech(|s| s.next_sentence()),
+            Message::PreviousSentence => self.move_speech(|s| {
+                s.previous_sentence();
+                true
+            }),
+            Message::Repeat => self.move_speech(|_| true),
+            Message::SpeechTick => {
+                if let Some(speech) = &mut self.speech
+                    && !speech.paused
+                    && self.utterance_started.elapsed() > UTTERANCE_GRACE
+                    && let Ok(false) = self.tts.is_speaking()
+                {
+                    if speech.next_sentence() {
+                        self.speak_current();
                     } else {
-                        WINDOW_SIZE
-                    },
+                        self.speech = None;
+                        return iced::window::resize(Id::MAIN, self.window_size());
+                    }
+                }
+                Command::none()
+            }
+            Message::PreviewTick => {
+                if self
+                    .preview_requested
+                    .is_some_and(|since| since.elapsed() >= SELECTION_PREVIEW_DELAY)
+                {
+                    self.preview_requested = None;
+                    if let Some((start, end)) = self.selection.confirm() {
+                        let capture = self.capture;
+                        return Command::perform(
+                            iced_logic::recognise_rect_in_background(
+                                self.engine.clone(),
+                                self.screenshot_buffer.clone(),
+                                self.screenshot_size,
+                                start,
+                                end,
+                            ),
+                            move |lines| Message::SelectionPreviewed(capture, (start, end), lines),
+                        );
+                    }
+                }
+                Command::none()
+            }
+            Message::SelectionPreviewed(capture, corners, lines) => {
+                // Drop text for a selection that has since been moved, resized or read
+                if capture == self.capture
+                    && self.preview_requested.is_none()
+                    && self.selection.confirm() == Some(corners)
+                {
+                    self.selection_preview = Some(lines.join(" "));
+                }
+                Command::none()
+            }
+            Message::HoverTick => {
+                if let Some((index, since)) = self.hovered_box
+                    && since.elapsed() >= HOVER_DWELL
+                {
+                    return self.read_text_box(index);
+                }
+                Command::none()
+            }
+            Message::Magnifier => {
+                self.magnifier_open = !self.magnifier_open;
+                self.lens = None;
+                self.lens_view = None;
+                let resize = iced::window::resize(Id::MAIN, self.window_size());
+                // Go back to where the bar was left once the magnifier stops following the mouse
+                if self.magnifier_position.take().is_some() {
+                    return Command::batch([
+                        resize,
+                        iced::window::move_to(Id::MAIN, self.settings.position.into()),
+                    ]);
+                }
+                resize
+            }
+            Message::MagnifierTick => {
+                let Some(centre) = iced_logic::get_mouse_position() else {
+                    return Command::none();
+                };
+                // Stay put while the mouse is on the window, so its controls can be used
+                let window = self.magnifier_position.unwrap_or(self.settings.position);
+                let size = self.window_size();
+                let over_window = (window.x..window.x + size.width as i32).contains(&centre.x)
+                    && (window.y..window.y + size.height as i32).contains(&centre.y);
+                if self.capturing_lens || over_window {
+                    return Command::none();
+                }
+                self.capturing_lens = true;
+                Command::perform(
+                    magnifier::capture_in_background(centre, Settings::clone(&self.settings)),
+                    move |result| Message::MagnifierCaptured(centre, result),
                 )
             }
+            Message::MagnifierCaptured(centre, result) => {
+                self.capturing_lens = false;
+                if !self.magnifier_open || self.screenshot_handle.is_some() {
+                    return Command::none();
+                }
+                match result {
+                    Ok(lens) => {
+                        self.lens_view = Some(Handle::from_pixels(
+                            lens.view.width(),
+                            lens.view.height(),
+                            lens.view.as_raw().clone(),
+                        ));
+                        self.lens = Some(lens);
+                    }
+                    Err(e) => {
+                        eprintln!("Error capturing magnifier: {e}");
+                        return Command::none();
+                    }
+                }
+                // Follow the mouse, beside the magnified area rather than over it
+                let monitors = iced_logic::monitor_bounds();
+                let Some(monitor) = monitors
+                    .iter()
+                    .find(|(left, top, right, bottom)| {
+                        (*left..*right).contains(&centre.x) && (*top..*bottom).contains(&centre.y)
+                    })
+                    .or(monitors.first())
+                else {
+                    return Command::none();
+                };
+                let size = self.window_size();
+                let position = magnifier::window_position(
+                    centre,
+                    self.settings.magnifier_zoom,
+                    (size.width as i32, size.height as i32),
+                    *monitor,
+                );
+                self.magnifier_position = Some(position);
+                iced::window::move_to(Id::MAIN, position.into())
+            }
+            Message::MagnifierZoom(zoom) => {
+                let zoom = zoom.clamp(magnifier::MIN_ZOOM, magnifier::MAX_ZOOM);
+                self.settings.commit(|s| s.magnifier_zoom = zoom);
+                self.save_settings();
+                Command::none()
+            }
+            Message::ReadMagnifier => {
+                if let Some(lens) = &self.lens {
+                    let lines = self.recognise_image(
+                        lens.captured.as_raw(),
+                        lens.captured.dimensions(),
+                        None,
+                    );
+                    let speak = self.speak_lines(lines, self.settings.reading_mode);
+                    return Command::batch([
+                        speak,
+                        iced::window::resize(Id::MAIN, self.window_size()),
+                    ]);
+                }
+                iced::window::resize(Id::MAIN, self.window_size())
+            }
+            Message::Reader => {
+                self.reader_open = !self.reader_open;
+                iced::window::resize(Id::MAIN, self.window_size())
+            }
+            Message::ReaderWord(index) => {
+                // Shift extends the selection from the word clicked before
+                self.reader_selection = match self.reader_selection {
+                    Some((first, _)) if self.modifiers.shift() => Some((first, index)),
+                    _ => Some((index, index)),
+                };
+                Command::none()
+            }
+            Message::SpeakReaderSelection => {
+                if let Some(reader) = &self.reader
+                    && let Some((first, last)) = self.reader_selection
+                {
+                    let (first, last) = (first.min(last), first.max(last));
+                    let words = reader_words(reader)
+                        .skip(first)
+                        .take(last - first + 1)
+                        .map(|(_, _, word)| word)
+                        .collect::<Vec<_>>();
+                    // The reader holds the text as recognised, so pronunciations are only applied once
+                    let speech = SpeechQueue::with_languages(vec![(words.join(" "), None)], |s| {
+                        self.lexicon.apply(s, self.settings.lexicon_ssml)
+                    });
+                    if !speech.is_empty() {
+                        self.speech = Some(speech);
+                        self.speak_current();
+                    }
+                }
+                iced::window::resize(Id::MAIN, self.window_size())
+            }
+            Message::Translated(translation, original, result) => {
+                if translation != self.translation {
+                    return Command::none();
+                }
+                match result {
+                    Ok(translated) => {
+                        let language = Some(self.settings.target_language.clone());
+                        self.queue_lines(translated, language, ReadingMode::Words);
+                    }
+                    Err(e) => {
+                        eprintln!("Error translating, reading the original text: {e}");
+                        self.queue_lines(original, None, ReadingMode::Words);
+                    }
+                }
+                iced::window::resize(Id::MAIN, self.window_size())
+            }
+            Message::ResizeReader => {
+                self.resizing_reader = true;
+                self.previous_drag_position = iced_logic::get_mouse_position();
+                Command::none()
+            }
+            Message::Settings => {
+                self.settings_open = !self.settings_open;
+                if !self.settings_open {
+                    self.preview_tts = None;
+                }
+                iced::window::resize(Id::MAIN, self.window_size())
+            }
             Message::SettingChanged(set_function) => {
-                self.settings_dirty = true;
-                set_function(&mut self.settings);
+                self.settings.change(|s| set_function(s), Instant::now());
+                Command::none()
+            }
+            Message::SettingsUndo => {
+                self.settings.undo();
                 Command::none()
             }
             Message::DragWindow => {
-                if let Some(ScreenPoint { x, y }) = iced_logic::get_mouse_position() {
-                    println!("Start drag at {x} {y}");
-                    self.previous_drag_position = Some(ScreenPoint { x: x, y: y });
+                println!("Start dragging window");
+                self.dragging_window = true;
+                iced::window::drag(Id::MAIN)
+            }
+            Message::WindowMoved(position) => {
+                // Moves to and from the screenshot overlay aren't the user's
+                if self.dragging_window {
+                    self.settings.commit(|s| s.position = position);
+                }
+                Command::none()
+            }
+            Message::WindowFocusChanged => {
+                // The system's window drag can swallow the mouse release, but the window's focus changes when it ends
+                if self.dragging_window {
+                    return self.finish_window_drag();
                 }
                 Command::none()
             }
             Message::ReleaseWindow => {
-                self.previous_drag_position = None;
+                self.resizing_reader = false;
+                // The reader panel was dragged to a new size
+                if self.previous_drag_position.take().is_some() {
+                    self.save_settings();
+                }
+                if self.dragging_window {
+                    return self.finish_window_drag();
+                }
+                Command::none()
+            }
+            Message::SwitchProfile(index) => {
+                // Switching commits straight away, which would throw away changes that haven't been applied
+                if self.settings.is_dirty() {
+                    let error = "Apply or cancel the changed settings before switching profiles";
+                    eprintln!("{error}");
+                    self.settings_error = Some(error.to_string());
+                    return Command::none();
+                }
+                if let Some(profile) = self.settings.profiles.get(index) {
+                    println!("Switching to profile {:?}", profile.name);
+                    let previous = self.settings.committed().clone();
+                    self.settings.commit(|s| {
+                        s.switch_profile(index);
+                    });
+                    self.load_models(&previous);
+                    self.save_settings();
+                    return self.reconfigure(&previous);
+                }
+                Command::none()
+            }
+            Message::NextProfile => {
+                let next = self
+                    .settings
+                    .active_profile_index()
+                    .map_or(0, |i| (i + 1) % self.settings.profiles.len());
+                self.update(Message::SwitchProfile(next))
+            }
+            Message::ProfileNameChanged(name) => {
+                self.profile_name = name;
+                Command::none()
+            }
+            Message::LanguageVoiceNameChanged(language) => {
+                self.language_voice_name = language;
+                Command::none()
+            }
+            Message::SaveProfile => {
+                let name = self.profile_name.trim().to_string();
+                if !name.is_empty() {
+                    self.settings
+                        .change(|s| s.save_profile(&name), Instant::now());
+                    self.profile_name.clear();
+                }
+                Command::none()
+            }
+            Message::DeleteProfile => {
+                if let Some(name) = self.settings.active_profile.clone() {
+                    self.settings
+                        .change(|s| s.delete_profile(&name), Instant::now());
+                }
+                Command::none()
+            }
+            Message::Lexicon => {
+                self.lexicon_open = !self.lexicon_open;
+                Command::none()
+            }
+            Message::LexiconChanged(set_function) => {
+                self.lexicon_dirty = true;
+                set_function(&mut self.lexicon);
+                Command::none()
+            }
+            Message::LexiconImport => {
+                if let Some(file) = FileDialog::new().add_filter("json", &["json"]).pick_file() {
+                    match Lexicon::import(&file) {
+                        Ok(lexicon) => {
+                            self.lexicon = lexicon;
+                            self.lexicon_dirty = true;
+                        }
+                        Err(e) => eprintln!("Error importing {:?}: {:?}", file, e),
+                    }
+                }
+                Command::none()
+            }
+            Message::LexiconExport => {
+                if let Some(file) = FileDialog::new()
+                    .add_filter("json", &["json"])
+                    .set_file_name("pronunciation.json")
+                    .save_file()
+                    && let Err(e) = self.lexicon.export(&file)
+                {
+                    eprintln!("Error exporting {:?}: {:?}", file, e);
+                }
                 Command::none()
             }
             Message::SettingsCancel => {
-                self.settings = Settings::default();
-                self.settings_dirty = false;
+                self.settings.cancel();
+                self.settings_error = None;
+                self.lexicon = Lexicon::load();
+                self.lexicon_dirty = false;
                 self.update(Message::Settings)
             }
+            Message::PreviewVoice => {
+                // Only one voice at a time, reading pauses rather than moving on to the next sentence
+                if let Some(speech) = &mut self.speech {
+                    speech.paused = true;
+                }
+                if let Err(e) = self.tts.stop() {
+                    eprintln!("Error pausing speaking: {:?}", e);
+                }
+                if let Some(previous) = &mut self.preview_tts {
+                    let _ = previous.stop();
+                }
+                // A backend of its own, so the settings can be heard without applying them
+                match iced_logic::init_tts(&self.settings, &Arc::default()) {
+                    Ok(mut preview) => {
+                        if let Err(e) = preview.speak(PREVIEW_PHRASE, true) {
+                            eprintln!("Error previewing voice: {:?}", e);
+                        }
+                        self.preview_tts = Some(preview);
+                    }
+                    Err(e) => {
+                        eprintln!("Error previewing voice: {e}");
+                        self.settings_error = Some(e);
+                    }
+                }
+                Command::none()
+            }
             Message::SettingError(e) => {
                 eprintln!("Error from settings: {:?}", e);
                 Command::none()
             }
+            Message::CheckSettingsFile => {
+                let modified = Settings::file_modified_time();
+                // Leave changes being made in the settings panel alone, apply or cancel will pick the file up
+                if modified == self.settings_modified || self.settings.is_dirty() {
+                    return Command::none();
+                }
+                self.settings_modified = modified;
+                match Settings::reload() {
+                    Ok(new_settings) => {
+                        println!("Settings file changed, reloading");
+                        self.settings_error = None;
+                        let previous = self.settings.replace(new_settings);
+                        self.load_models(&previous);
+                        self.reconfigure(&previous)
+                    }
+                    Err(e) => {
+                        eprintln!("Error reloading settings: {e}");
+                        self.settings_error = Some(e);
+                        Command::none()
+                    }
+                }
+            }
             Message::SettingsApply => {
-                let _ = self.settings.save_to_file();
-                self.engine = iced_logic::init_engine(&self.settings);
-                self.tts = iced_logic::init_tts(&self.settings);
-                self.settings_dirty = false;
-                self.update(Message::Settings)
+                self.settings_error = None;
+                // Load new models before committing to them, so a file that can't be loaded is never saved
+                let committed = self.settings.committed().clone();
+                self.load_models(&committed);
+                let previous = self.settings.apply();
+                // Saved from each layer's own values, so overridden settings don't end up in the profile on disk
+                if let Some(name) = self.settings.active_profile.clone() {
+                    self.settings.commit(|s| s.save_profile(&name));
+                }
+                self.save_settings();
+                if let Err(e) = self.lexicon.save_to_file() {
+                    eprintln!("Error saving pronunciations: {:?}", e);
+                }
+                self.lexicon_dirty = false;
+                let reconfigure = self.reconfigure(&previous);
+                Command::batch([reconfigure, self.update(Message::Settings)])
             }
             Message::Quit => iced::window::close(Id::MAIN),
         }
     }
     fn new(_flags: Self::Flags) -> (Self, iced::Command<Message>) {
+        let app = Self::default();
+        // The monitor it was saved on may have been unplugged since
+        let position = iced_logic::dock_window(
+            app.settings.position,
+            (WINDOW_SIZE.width as i32, WINDOW_SIZE.height as i32),
+            SNAP_DISTANCE,
+        );
         (
-            Self::default(),
+            app,
             Command::batch([
                 iced::window::resize(Id::MAIN, WINDOW_SIZE),
-                iced::window::move_to(Id::MAIN, Self::default().settings.position.into()),
+                iced::window::move_to(Id::MAIN, position.into()),
             ]),
         )
     }
@@ -307,145 +1046,908 @@ impl Application for IcedApp {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        event::listen_with(|evt, _| {
-            if let iced::Event::Mouse(iced::mouse::Event::CursorMoved { .. }) = evt {
-                if let Some(p) = iced_logic::get_mouse_position() {
-                    Some(Message::MouseMoved(p))
-                } else {
-                    None
-                }
-            } else {
-                None
+        let speech_poll = match &self.speech {
+            Some(speech) if !speech.paused => {
+                iced::time::every(SPEECH_POLL_INTERVAL).map(|_| Message::SpeechTick)
             }
-        })
+            _ => Subscription::none(),
+        };
+
+        // The arrow keys move the selection while the screenshot is showing
+        let key_presses = if self.selection.is_open() {
+            keyboard::on_key_press(overlay_hotkey)
+        } else {
+            keyboard::on_key_press(hotkey)
+        };
+
+        let magnifier_poll = if self.magnifier_open && self.screenshot_handle.is_none() {
+            iced::time::every(MAGNIFIER_INTERVAL).map(|_| Message::MagnifierTick)
+        } else {
+            Subscription::none()
+        };
+
+        let preview_poll = if self.preview_requested.is_some() {
+            iced::time::every(SPEECH_POLL_INTERVAL).map(|_| Message::PreviewTick)
+        } else {
+            Subscription::none()
+        };
+
+        let hover_poll = if self.settings.hover_to_read && self.hovered_box.is_some() {
+            iced::time::every(SPEECH_POLL_INTERVAL).map(|_| Message::HoverTick)
+        } else {
+            Subscription::none()
+        };
+
+        Subscription::batch([
+            event::listen_with(|evt, _| match evt {
+                iced::Event::Mouse(iced::mouse::Event::CursorMoved { .. }) => {
+                    if let Some(p) = iced_logic::get_mouse_position() {
+                        Some(Message::MouseMoved(p))
+                    } else {
+                        None
+                    }
+                }
+                iced::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                    Some(Message::ModifiersChanged(modifiers))
+                }
+                iced::Event::Window(_, iced::window::Event::Moved { x, y }) => {
+                    Some(Message::WindowMoved(ScreenPoint { x, y }))
+                }
+                iced::Event::Window(
+                    _,
+                    iced::window::Event::Focused | iced::window::Event::Unfocused,
+                ) => Some(Message::WindowFocusChanged),
+                iced::Event::Mouse(iced::mouse::Event::ButtonReleased(
+                    iced::mouse::Button::Left,
+                )) => Some(Message::ReleaseWindow),
+                _ => None,
+            }),
+            key_presses,
+            speech_poll,
+            hover_poll,
+            preview_poll,
+            magnifier_poll,
+            iced::time::every(SETTINGS_POLL_INTERVAL).map(|_| Message::CheckSettingsFile),
+        ])
     }
 }
 
 impl Default for IcedApp {
     fn default() -> Self {
-        let settings = Settings::default();
+        let settings = SettingsState::with_overrides(
+            Settings::default(),
+            overrides::get().cloned().unwrap_or_default(),
+        );
+        let utterances = Arc::<Utterances>::default();
         Self {
-            engine: iced_logic::init_engine(&settings),
-            tts: iced_logic::init_tts(&settings),
-            screenshot_buffer: vec![],
+            engine: Arc::new(
+                iced_logic::init_engine(&settings).expect("Failed to load OCR models"),
+            ),
+            // Settings the backend rejects shouldn't stop the app opening, start with its defaults instead
+            tts: iced_logic::init_tts(&settings, &utterances)
+                .or_else(|e| {
+                    eprintln!("{e}, using the default voice");
+                    iced_logic::init_tts(&Settings::initial(), &utterances)
+                })
+                .expect("Failed to start Text-to-Speech"),
+            utterances,
+            preview_tts: None,
+            screenshot_buffer: Arc::new(vec![]),
             screenshot_size: (0, 0),
-            rect_start: None,
-            rect_end: None,
+            screenshot_buffer_size: (0, 0),
+            screenshot_origin: ScreenPoint::default(),
+            capture: 0,
+            selection: Selection::Closed,
+            text_lines: None,
+            snapped_line: None,
+            text_boxes: vec![],
+            hovered_box: None,
+            selection_preview: None,
+            preview_requested: None,
+            lasso: vec![],
+
+            screenshot_handle: None,
 
-            screenshot_image: None,
+            magnifier_open: false,
+            lens: None,
+            lens_view: None,
+            capturing_lens: false,
+            magnifier_position: None,
 
-            settings: settings,
+            speech: None,
+            reader: None,
+            reader_open: false,
+            reader_selection: None,
+            resizing_reader: false,
+            utterance_started: Instant::now(),
+            voice_language: None,
+            translation: 0,
+            modifiers: keyboard::Modifiers::default(),
+
+            settings,
+            settings_modified: Settings::file_modified_time(),
+            settings_error: None,
+            lexicon: Lexicon::load(),
             settings_open: false,
-            settings_dirty: false,
+            lexicon_dirty: false,
+            lexicon_open: false,
+            profile_name: String::new(),
+            language_voice_name: String::new(),
             previous_drag_position: None,
+            dragging_window: false,
         }
     }
 }
 
 impl IcedApp {
-    fn speak_from_rect(
-        &mut self,
-        screenshot: Vec<u8>,
-        screenshot_size: (u32, u32),
-        first_corner: ScreenPoint,
-        second_corner: ScreenPoint,
-    ) {
+    /// Recognises the lines of text in the screenshot between the two corners
+    fn recognise_rect(&self, first_corner: ScreenPoint, second_corner: ScreenPoint) -> Vec<String> {
         let (img_source_bytes, new_width, new_height) = iced_logic::get_cropped_image_source(
-            screenshot,
-            screenshot_size,
+            self.screenshot_buffer.to_vec(),
+            self.screenshot_size,
             first_corner,
             second_corner,
         );
-        let img_source =
-            ImageSource::from_bytes(&img_source_bytes[..], (new_width, new_height)).unwrap();
+        self.recognise_image(&img_source_bytes, (new_width, new_height), None)
+    }
+
+    /// Recognises the lines of text inside a lasso, with everything outside it masked out
+    fn recognise_polygon(&self, polygon: &[ScreenPoint]) -> Vec<String> {
+        let (img_source_bytes, new_width, new_height, shape) = iced_logic::get_masked_image_source(
+            self.screenshot_buffer.to_vec(),
+            self.screenshot_size,
+            polygon,
+        );
+        self.recognise_image(
+            &img_source_bytes,
+            (new_width, new_height),
+            self.settings.lasso_whole_words.then_some(&shape),
+        )
+    }
 
-        let ocr_input = self.engine.prepare_input(img_source).unwrap();
+    /// Recognises the lines of text in an image, keeping only words entirely inside `within` if given
+    fn recognise_image(
+        &self,
+        img_source_bytes: &[u8],
+        size: (u32, u32),
+        within: Option<&Polygon>,
+    ) -> Vec<String> {
+        iced_logic::recognise_image(&self.engine, img_source_bytes, size, within)
+    }
 
-        // Get oriented bounding boxes of text words in input image.
-        let word_rects = self.engine.detect_words(&ocr_input).unwrap();
+    /// Reads out recognised `lines`, translating them first in the background if that's turned on
+    fn speak_lines(&mut self, lines: Vec<String>, reading_mode: ReadingMode) -> Command<Message> {
+        if self.settings.translate && reading_mode == ReadingMode::Words && !lines.is_empty() {
+            self.translation += 1;
+            let translation = self.translation;
+            return Command::perform(
+                translate::translate_in_background(
+                    self.settings.translation_endpoint.clone(),
+                    self.settings.source_language.clone(),
+                    self.settings.target_language.clone(),
+                    lines.clone(),
+                ),
+                move |result| Message::Translated(translation, lines, result),
+            );
+        }
+        self.queue_lines(lines, None, reading_mode);
+        Command::none()
+    }
 
-        // Group words into lines. Each line is represented by a list of word
-        // bounding boxes.
-        let line_rects = self.engine.find_text_lines(&ocr_input, &word_rects);
+    /// Starts speaking `lines` in `language`, or the language they were recognised in for `None`
+    fn queue_lines(
+        &mut self,
+        lines: Vec<String>,
+        language: Option<String>,
+        reading_mode: ReadingMode,
+    ) {
+        // Read in a voice for the language being read, if we know it
+        let detect_language = language.is_none()
+            && self.settings.detect_language
+            && reading_mode == ReadingMode::Words;
+        let text_language =
+            language.or_else(|| self.settings.recognition_language().map(|l| l.to_string()));
 
-        let words = self
-            .engine
-            .recognize_text(&ocr_input, &line_rects[..])
-            .unwrap()
+        let lines = lines
             .into_iter()
-            .map(|x| x.map_or("".to_string(), |x| x.to_string()))
-            .collect::<Vec<_>>()
-            .join(" ");
+            .map(|x| {
+                let line_language = if detect_language {
+                    language::detect(&x)
+                        .map(|l| l.to_string())
+                        .or(text_language.clone())
+                } else {
+                    text_language.clone()
+                };
+                (x, line_language)
+            })
+            .collect::<Vec<_>>();
 
-        println!("Speaking {words}");
-        self.tts.speak(words, false).unwrap();
+        println!(
+            "Speaking {}",
+            lines
+                .iter()
+                .map(|(l, _)| l.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        // The reader shows the text as recognised, only the voice gets pronunciations or spelling
+        let speech = SpeechQueue::with_languages(lines, |sentence| match reading_mode {
+            ReadingMode::Words => self.lexicon.apply(sentence, self.settings.lexicon_ssml),
+            ReadingMode::Spell => speech::spell_out(sentence, self.settings.phonetic_alphabet),
+        });
+        if !speech.is_empty() {
+            self.reader = Some(speech.clone());
+            self.reader_selection = None;
+            self.speech = Some(speech);
+            self.speak_current();
+        }
     }
-}
 
-fn settings_widget(app: &IcedApp) -> Element<'_, Message> {
-    if app.settings_open {
-        let rect_colour = app.settings.rect_colour.clone();
-        column([
-            // top rule
-            horizontal_rule(2).into(),
-            if app.settings_dirty {
-                row([
-                    widget::button("CANCEL")
-                        .on_press(Message::SettingsCancel)
-                        .into(),
-                    widget::button("APPLY")
-                        .on_press(Message::SettingsApply)
-                        .into(),
-                ])
-                .into()
-            } else {
-                horizontal_rule(0).into()
-            },
-            // Rate slider
-            row([
-                widget::text(app.settings.rate)
-                    .width(70)
-                    .horizontal_alignment(Horizontal::Right)
-                    .into(),
-                vertical_rule(2).into(),
-                iced::widget::slider(
-                    VoiceRate::Slowest..=VoiceRate::TooFast,
-                    app.settings.rate,
-                    |new_value| {
-                        if new_value != app.settings.rate {
-                            println!("Setting s.rate to {:?}", new_value);
-                            Message::SettingChanged(Arc::new(move |s: &mut Settings| {
-                                s.rate = new_value
-                            }))
-                        } else {
-                            Message::SettingChanged(Arc::new(move |_: &mut Settings| {}))
-                        }
+    /// Switches to a voice for `language` if one is installed, or back to the chosen voice for `None`
+    /// Languages with a voice chosen in `language_voices` use that voice
+    fn use_voice_for(&mut self, language: Option<&str>) {
+        if self.voice_language.as_deref() == language {
+            return;
+        }
+        self.voice_language = language.map(|l| l.to_string());
+        let voices = self.tts.voices().unwrap_or_default();
+        let voice = language
+            .and_then(|language| {
+                iced_logic::voice_for_language(&self.tts, language, &self.settings)
+            })
+            .or_else(|| voices.into_iter().find(|v| v.id() == self.settings.voice));
+        let Some(voice) = voice else {
+            return;
+        };
+        if let Err(e) = self.tts.set_voice(&voice) {
+            eprintln!("Error setting voice to {:?}: {:?}", voice.name(), e);
+        }
+    }
+
+    fn speak_current(&mut self) {
+        let language = self
+            .speech
+            .as_ref()
+            .and_then(|s| s.language())
+            .map(|l| l.to_string());
+        self.use_voice_for(language.as_deref());
+        if let Some(sentence) = self.speech.as_ref().and_then(|s| s.current()) {
+            if let Err(e) = self.tts.speak(sentence, true) {
+                eprintln!("Error speaking {:?}: {:?}", sentence, e);
+            }
+            self.utterance_started = Instant::now();
+        }
+    }
+
+    /// Moves the speech cursor with `f` and speaks from there, finishing if `f` runs off the end
+    fn move_speech(&mut self, f: impl FnOnce(&mut SpeechQueue) -> bool) -> Command<Message> {
+        if let Some(speech) = &mut self.speech {
+            if !f(speech) {
+                return self.update(Message::Stop);
+            }
+            if !speech.paused {
+                self.speak_current();
+            }
+        }
+        Command::none()
+    }
+
+    /// Holding shift spells out this capture regardless of the reading mode setting
+    fn capture_reading_mode(&self) -> ReadingMode {
+        if self.modifiers.shift() {
+            ReadingMode::Spell
+        } else {
+            self.settings.reading_mode
+        }
+    }
+
+    fn read_selection(&mut self, (start, end): (ScreenPoint, ScreenPoint)) -> Command<Message> {
+        let lines = self.recognise_rect(start, end);
+        let speak = self.speak_lines(lines, self.capture_reading_mode());
+        Command::batch([speak, self.close_overlay()])
+    }
+
+    /// Starts a lasso, or adds a point to a polygon being clicked out without `drag_draw`
+    fn lasso_click(&mut self, pos: ScreenPoint) -> Command<Message> {
+        if !self.settings.drag_draw
+            && let Selection::Dragging { .. } = self.selection
+            && let Some(first) = self.lasso.first()
+        {
+            // Clicking back on the first point closes the polygon
+            if self.lasso.len() >= 3
+                && (first.x - pos.x).abs() <= selection::HANDLE_GRAB_DISTANCE
+                && (first.y - pos.y).abs() <= selection::HANDLE_GRAB_DISTANCE
+            {
+                return self.finish_lasso();
+            }
+            self.lasso.push(pos);
+        } else {
+            // Lassos can't be adjusted, so always start a new one
+            self.selection = Selection::Empty;
+            self.selection.press(pos);
+            self.selection_preview = None;
+            self.lasso = vec![pos];
+        }
+        Command::none()
+    }
+
+    /// Which of the words or lines found up front is under `pos`, if any
+    fn text_box_at(&self, pos: ScreenPoint) -> Option<usize> {
+        self.text_boxes.iter().position(|(top_left, bottom_right)| {
+            let (top_left, bottom_right) = (
+                self.to_screen_point(*top_left),
+                self.to_screen_point(*bottom_right),
+            );
+            (top_left.x..=bottom_right.x).contains(&pos.x)
+                && (top_left.y..=bottom_right.y).contains(&pos.y)
+        })
+    }
+
+    fn read_text_box(&mut self, index: usize) -> Command<Message> {
+        let (top_left, bottom_right) = self.text_boxes[index];
+        let (top_left, bottom_right) = (
+            self.to_screen_point(top_left),
+            self.to_screen_point(bottom_right),
+        );
+        self.read_selection((
+            self.clamp_to_screenshot(ScreenPoint {
+                x: top_left.x - TEXT_BOX_PADDING,
+                y: top_left.y - TEXT_BOX_PADDING,
+            }),
+            self.clamp_to_screenshot(ScreenPoint {
+                x: bottom_right.x + TEXT_BOX_PADDING,
+                y: bottom_right.y + TEXT_BOX_PADDING,
+            }),
+        ))
+    }
+
+    fn finish_lasso(&mut self) -> Command<Message> {
+        let lasso = std::mem::take(&mut self.lasso);
+        match selection::bounding_box(&lasso) {
+            Some((top_left, bottom_right))
+                if lasso.len() >= 3 && selection::is_large_enough(top_left, bottom_right) =>
+            {
+                let lines = self.recognise_polygon(&lasso);
+                let speak = self.speak_lines(lines, self.capture_reading_mode());
+                Command::batch([speak, self.close_overlay()])
+            }
+            _ => {
+                println!("Lasso too small to read");
+                self.selection = Selection::Empty;
+                Command::none()
+            }
+        }
+    }
+
+    /// Ends a drag, reading the selection straight away or leaving it to be adjusted
+    fn finish_selection(&mut self) -> Command<Message> {
+        match self.selection.release() {
+            Some(corners) if self.settings.read_on_release => self.read_selection(corners),
+            _ => {
+                self.update_preview();
+                Command::none()
+            }
+        }
+    }
+
+    /// Recognises the text in a selection that is waiting to be confirmed once it stops changing,
+    /// so it can be checked before reading without running OCR on every key press
+    fn update_preview(&mut self) {
+        self.selection_preview = None;
+        self.preview_requested = self.selection.confirm().map(|_| Instant::now());
+    }
+
+    fn close_overlay(&mut self) -> Command<Message> {
+        self.selection = Selection::Closed;
+        self.selection_preview = None;
+        self.preview_requested = None;
+        self.lasso.clear();
+        self.text_boxes.clear();
+        self.hovered_box = None;
+        self.screenshot_handle = None;
+        Command::batch([
+            iced::window::resize(Id::MAIN, self.window_size()),
+            iced::window::move_to(Id::MAIN, self.settings.position.into()),
+        ])
+    }
+
+    /// What the overlay draws over the screenshot for the current selection
+    fn selection_shapes(&self) -> Vec<Shape> {
+        let mut shapes = self
+            .text_boxes
+            .iter()
+            .enumerate()
+            .map(|(index, (top_left, bottom_right))| Shape::Rectangle {
+                top_left: *top_left,
+                bottom_right: *bottom_right,
+                // Mark the one under the mouse so it's clear what a click will read
+                handles: self
+                    .hovered_box
+                    .is_some_and(|(hovered, _)| hovered == index),
+            })
+            .collect::<Vec<_>>();
+        if self.settings.selection_shape == SelectionShape::Lasso
+            && let Selection::Dragging { end, .. } = self.selection
+            && !self.lasso.is_empty()
+        {
+            // Join the points up to the mouse and back to the start, to show the shape that will be read
+            shapes.push(Shape::Path(
+                self.lasso
+                    .iter()
+                    .chain([&end, &self.lasso[0]])
+                    .map(|p| iced_logic::get_image_coords(*p, self.screenshot_size))
+                    .collect(),
+            ));
+        } else if let Some((rect_start, rect_end)) = self.selection.corners() {
+            let img_coord_start = iced_logic::get_image_coords(rect_start, self.screenshot_size);
+            let img_coord_end = iced_logic::get_image_coords(rect_end, self.screenshot_size);
+
+            shapes.push(Shape::Selection {
+                top_left: get_top_left(img_coord_start, img_coord_end),
+                bottom_right: get_bottom_right(img_coord_start, img_coord_end),
+                handles: !matches!(self.selection, Selection::Dragging { .. }),
+            });
+        }
+        shapes
+    }
+
+    /// The current selection, or a new one around the mouse if the arrow keys haven't been used yet
+    fn keyboard_selection(&self) -> (ScreenPoint, ScreenPoint) {
+        if let Some(corners) = self.selection.corners() {
+            return corners;
+        }
+        let centre = iced_logic::get_mouse_position().unwrap_or(self.screenshot_origin);
+        let (width, height) = KEYBOARD_SELECTION_SIZE;
+        (
+            self.clamp_to_screenshot(ScreenPoint {
+                x: centre.x - width / 2,
+                y: centre.y - height / 2,
+            }),
+            self.clamp_to_screenshot(ScreenPoint {
+                x: centre.x + width / 2,
+                y: centre.y + height / 2,
+            }),
+        )
+    }
+
+    /// The top left and bottom right pixels of the screenshot, in screen coordinates
+    fn screenshot_bounds(&self) -> (ScreenPoint, ScreenPoint) {
+        (
+            self.screenshot_origin,
+            ScreenPoint {
+                x: self.screenshot_origin.x + self.screenshot_size.0 as i32 - 1,
+                y: self.screenshot_origin.y + self.screenshot_size.1 as i32 - 1,
+            },
+        )
+    }
+
+    fn clamp_to_screenshot(&self, point: ScreenPoint) -> ScreenPoint {
+        let (min, max) = self.screenshot_bounds();
+        ScreenPoint {
+            x: point.x.clamp(min.x, max.x),
+            y: point.y.clamp(min.y, max.y),
+        }
+    }
+
+    fn to_screen_point(&self, point: ImagePoint) -> ScreenPoint {
+        ScreenPoint {
+            x: self.screenshot_origin.x + point.x as i32,
+            y: self.screenshot_origin.y + point.y as i32,
+        }
+    }
+
+    /// Keeps the window on screen and against any edge it was dropped near, then saves where it ended up
+    fn finish_window_drag(&mut self) -> Command<Message> {
+        self.dragging_window = false;
+        let size = self.window_size();
+        let position = iced_logic::dock_window(
+            self.settings.position,
+            (size.width as i32, size.height as i32),
+            SNAP_DISTANCE,
+        );
+        println!("Window dropped at {position}");
+        self.settings.commit(|s| s.position = position);
+        self.save_settings();
+        iced::window::move_to(Id::MAIN, position.into())
+    }
+
+    /// Writes the committed settings, leaving any changes still pending in the settings panel out
+    fn save_settings(&mut self) {
+        if let Err(e) = self.settings.file().save_to_file() {
+            eprintln!("Error saving settings: {e}");
+        }
+        // Don't pick our own write up as an outside change
+        self.settings_modified = Settings::file_modified_time();
+    }
+
+    /// Loads the OCR models the settings point to if they changed from `previous`. If they can't be loaded,
+    /// the old models stay in use and their files are put back in the settings, so trying again reloads them
+    fn load_models(&mut self, previous: &Settings) {
+        if previous.detection_file == self.settings.detection_file
+            && previous.recognition_file == self.settings.recognition_file
+        {
+            return;
+        }
+        match iced_logic::init_engine(&self.settings) {
+            Ok(engine) => self.engine = Arc::new(engine),
+            Err(e) => {
+                eprintln!("Error loading OCR models: {e}");
+                self.settings_error = Some(e);
+                self.settings.commit(|s| {
+                    s.detection_file = previous.detection_file.clone();
+                    s.recognition_file = previous.recognition_file.clone();
+                });
+            }
+        }
+    }
+
+    /// Brings the voice and window in line with committed settings that changed from `previous`.
+    /// The voice is adjusted without restarting the backend, the OCR models are handled by `load_models`
+    fn reconfigure(&mut self, previous: &Settings) -> Command<Message> {
+        let settings = self.settings.committed();
+        if previous.voice != settings.voice
+            || previous.pitch != settings.pitch
+            || previous.rate != settings.rate
+            || previous.volume != settings.volume
+            || previous.language_voices != settings.language_voices
+        {
+            if let Err(e) = iced_logic::configure_tts(&mut self.tts, settings) {
+                eprintln!("Error changing the voice: {e}");
+            }
+            self.voice_language = None;
+        }
+        // The rectangle colour and reading options are read as they're used
+        if previous.position != settings.position && self.screenshot_handle.is_none() {
+            return iced::window::move_to(Id::MAIN, settings.position.into());
+        }
+        Command::none()
+    }
+
+    fn window_size(&self) -> Size {
+        let mut bar_size = if self.speech.is_some() {
+            WINDOW_SIZE_SPEAKING
+        } else {
+            WINDOW_SIZE
+        };
+        if !self.settings.profiles.is_empty() {
+            bar_size.width += PROFILE_BUTTON_WIDTH;
+        }
+        if self.speech.is_none() && self.reader.is_some() {
+            bar_size.width += READER_BUTTON_WIDTH;
+        }
+        if self.magnifier_open {
+            bar_size = Size::new(
+                bar_size.width.max(magnifier::LENS_SIZE.0 as f32),
+                bar_size.height + MAGNIFIER_CONTROLS_HEIGHT + magnifier::LENS_SIZE.1 as f32,
+            );
+        }
+        if self.reader_open && self.reader.is_some() {
+            bar_size = Size::new(
+                bar_size.width.max(self.settings.reader_width as f32),
+                bar_size.height + self.settings.reader_height as f32,
+            );
+        }
+        if self.settings_open {
+            Size::new(
+                bar_size.width.max(WINDOW_SIZE_SETTINGS.width),
+                WINDOW_SIZE_SETTINGS.height
+                    + LANGUAGE_VOICE_ROW_HEIGHT * self.settings.language_voices.len() as f32
+                    + bar_size.height
+                    - WINDOW_SIZE.height,
+            )
+        } else {
+            bar_size
+        }
+    }
+}
+
+/// Arrow keys move the selection, shift+arrows resize it, tab snaps it to the next line of text
+fn overlay_hotkey(key: Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    let step = if modifiers.alt() { 1 } else { KEYBOARD_STEP };
+    let (dx, dy) = match key.as_ref() {
+        Key::Named(Named::ArrowLeft) => (-step, 0),
+        Key::Named(Named::ArrowRight) => (step, 0),
+        Key::Named(Named::ArrowUp) => (0, -step),
+        Key::Named(Named::ArrowDown) => (0, step),
+        Key::Named(Named::Tab) => return Some(Message::SnapSelection(!modifiers.shift())),
+        Key::Named(Named::Enter) => return Some(Message::ConfirmSelection),
+        Key::Named(Named::Escape) => return Some(Message::CancelSelection),
+        _ => return None,
+    };
+    if modifiers.shift() {
+        Some(Message::ResizeSelection(dx, dy))
+    } else {
+        Some(Message::MoveSelection(dx, dy))
+    }
+}
+
+fn hotkey(key: Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    match key.as_ref() {
+        // Ctrl+1 to Ctrl+9 switch to the numbered profile
+        Key::Character(c) if modifiers.command() => c
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .map(|n| Message::SwitchProfile(n - 1)),
+        Key::Named(Named::Space) => Some(Message::TogglePause),
+        Key::Named(Named::Escape) => Some(Message::Stop),
+        Key::Named(Named::ArrowUp) => Some(Message::PreviousLine),
+        Key::Named(Named::ArrowDown) => Some(Message::NextLine),
+        Key::Named(Named::ArrowLeft) => Some(Message::PreviousSentence),
+        Key::Named(Named::ArrowRight) => Some(Message::NextSentence),
+        Key::Character("r") => Some(Message::Repeat),
+        _ => None,
+    }
+}
+
+/// Cycles through profiles, labelled with the first letter of the active one
+fn profile_button(app: &IcedApp) -> Option<Element<'_, Message>> {
+    if app.settings.profiles.is_empty() {
+        return None;
+    }
+    let label = app
+        .settings
+        .active_profile
+        .as_ref()
+        .and_then(|name| name.chars().next())
+        .map_or("P".to_string(), |c| c.to_uppercase().to_string());
+    Some(button(text(label)).on_press(Message::NextProfile).into())
+}
+
+/// Zoom controls and the magnified view of the screen around the mouse
+fn magnifier_widget(app: &IcedApp) -> Element<'_, Message> {
+    if !app.magnifier_open {
+        return horizontal_rule(0).into();
+    }
+    let zoom = app.settings.magnifier_zoom;
+    column([
+        row([
+            button("-")
+                .on_press(Message::MagnifierZoom(zoom - MAGNIFIER_ZOOM_STEP))
+                .into(),
+            text(format!("{zoom:.1}x")).width(40).into(),
+            button("+")
+                .on_press(Message::MagnifierZoom(zoom + MAGNIFIER_ZOOM_STEP))
+                .into(),
+            button("Read lens")
+                .on_press_maybe(app.lens.as_ref().map(|_| Message::ReadMagnifier))
+                .into(),
+        ])
+        .spacing(2)
+        .height(MAGNIFIER_CONTROLS_HEIGHT)
+        .into(),
+        match &app.lens_view {
+            Some(view) => widget::image(view.clone())
+                .width(magnifier::LENS_SIZE.0 as f32)
+                .height(magnifier::LENS_SIZE.1 as f32)
+                .into(),
+            None => text("...").into(),
+        },
+    ])
+    .into()
+}
+
+/// Every word of the text with the line and sentence it is in, as shown in the reader panel
+fn reader_words(reader: &SpeechQueue) -> impl Iterator<Item = (usize, usize, String)> + '_ {
+    reader
+        .text()
+        .iter()
+        .enumerate()
+        .flat_map(|(line, sentences)| {
+            sentences
+                .iter()
+                .enumerate()
+                .flat_map(move |(sentence, text)| {
+                    text.split_whitespace()
+                        .map(|word| (line, sentence, word.to_string()))
+                        .collect::<Vec<_>>()
+                })
+        })
+}
+
+/// The last text read in large print, highlighting the sentence being spoken.
+/// Words can be clicked, or shift+clicked to select several, and the selection spoken again
+fn reader_widget(app: &IcedApp) -> Element<'_, Message> {
+    let Some(reader) = app.reader.as_ref().filter(|_| app.reader_open) else {
+        return horizontal_rule(0).into();
+    };
+    let (text_colour, background, highlight) = app.settings.reader_colours.colours();
+    let font_size = app.settings.reader_font_size as f32;
+    let line_height = iced::widget::text::LineHeight::Relative(app.settings.reader_line_spacing);
+
+    // Backends don't say which word they're on, so the whole sentence is highlighted. Those with
+    // utterance callbacks say when it's heard, so the highlight waits for the voice to start
+    let audible = !app.tts.supported_features().utterance_callbacks || app.utterances.in_progress();
+    let spoken = app
+        .speech
+        .as_ref()
+        .filter(|speech| audible && !speech.paused && speech.same_text(reader))
+        .map(|speech| (speech.line, speech.sentence));
+    let selection = app
+        .reader_selection
+        .map(|(first, last)| first.min(last)..=first.max(last));
+
+    // Rows don't wrap, so break lines by an estimate of each word's width
+    let available_width = app.settings.reader_width as f32 - 2. * READER_PADDING;
+    let mut lines: Vec<Vec<Element<'_, Message>>> = vec![];
+    let mut line_width = f32::MAX;
+    let mut previous_line = None;
+    for (index, (line, sentence, word)) in reader_words(reader).enumerate() {
+        let width = (word.chars().count() + 1) as f32 * font_size * 0.55;
+        if previous_line != Some(line) || line_width + width > available_width {
+            lines.push(vec![]);
+            line_width = 0.;
+        }
+        previous_line = Some(line);
+        line_width += width;
+
+        let (colour, word_background) = if spoken == Some((line, sentence)) {
+            (text_colour, highlight)
+        } else if selection.as_ref().is_some_and(|s| s.contains(&index)) {
+            (background, text_colour)
+        } else {
+            (text_colour, background)
+        };
+
+        lines.last_mut().unwrap().push(
+            mouse_area(
+                widget::container(
+                    text(format!("{word} "))
+                        .size(font_size)
+                        .line_height(line_height)
+                        .style(colour),
+                )
+                .style(move |_theme: &Theme| Appearance {
+                    background: Some(iced::Background::Color(word_background)),
+                    ..Appearance::default()
+                }),
+            )
+            .on_press(Message::ReaderWord(index))
+            .into(),
+        );
+    }
+
+    let controls = row([
+        button("Speak selection")
+            .on_press_maybe(app.reader_selection.map(|_| Message::SpeakReaderSelection))
+            .into(),
+        widget::horizontal_space().into(),
+        mouse_area(text("◢").size(20))
+            .on_press(Message::ResizeReader)
+            .on_release(Message::ReleaseWindow)
+            .interaction(iced::mouse::Interaction::Grab)
+            .into(),
+    ]);
+
+    widget::container(column([
+        widget::scrollable(column(lines.into_iter().map(|words| row(words).into())))
+            .height(iced::Length::Fill)
+            .into(),
+        controls.into(),
+    ]))
+    .width(app.settings.reader_width as f32)
+    .height(app.settings.reader_height as f32)
+    .padding(READER_PADDING)
+    .style(move |_theme: &Theme| Appearance {
+        background: Some(iced::Background::Color(background)),
+        text_color: Some(text_colour),
+        ..Appearance::default()
+    })
+    .into()
+}
+
+fn settings_widget(app: &IcedApp) -> Element<'_, Message> {
+    if app.settings_open && app.lexicon_open {
+        lexicon_widget(app)
+    } else if app.settings_open {
+        let rect_colour = app.settings.rect_colour.clone();
+        column([
+            // top rule
+            horizontal_rule(2).into(),
+            if app.settings.is_dirty() || app.lexicon_dirty {
+                row([
+                    widget::button("CANCEL")
+                        .on_press(Message::SettingsCancel)
+                        .into(),
+                    widget::button("UNDO")
+                        .on_press_maybe(app.settings.can_undo().then_some(Message::SettingsUndo))
+                        .into(),
+                    widget::button("APPLY")
+                        .on_press(Message::SettingsApply)
+                        .into(),
+                ])
+                .into()
+            } else {
+                horizontal_rule(0).into()
+            },
+            if let Some(error) = &app.settings_error {
+                widget::text(error)
+                    .size(12)
+                    .style(Color::from_rgb8(200, 0, 0))
+                    .into()
+            } else {
+                horizontal_rule(0).into()
+            },
+            // Profile picker
+            row([
+                iced::widget::pick_list(
+                    app.settings
+                        .profiles
+                        .iter()
+                        .map(|p| p.name.clone())
+                        .collect::<Vec<_>>(),
+                    app.settings.active_profile.clone(),
+                    |new_value| {
+                        Message::SwitchProfile(
+                            app.settings
+                                .profiles
+                                .iter()
+                                .position(|p| p.name == new_value)
+                                .unwrap_or_default(),
+                        )
                     },
                 )
+                .placeholder("Profile")
+                .width(120)
+                .into(),
+                widget::button("Delete")
+                    .on_press_maybe(
+                        app.settings
+                            .active_profile
+                            .as_ref()
+                            .map(|_| Message::DeleteProfile),
+                    )
+                    .into(),
+            ])
+            .into(),
+            row([
+                widget::text_input("New profile", &app.profile_name)
+                    .on_input(Message::ProfileNameChanged)
+                    .on_submit(Message::SaveProfile)
+                    .width(120)
+                    .into(),
+                widget::button("Save").on_press(Message::SaveProfile).into(),
+            ])
+            .into(),
+            // Rate slider
+            row([
+                widget::text(format!("Rate {}%", app.settings.rate))
+                    .width(70)
+                    .horizontal_alignment(Horizontal::Right)
+                    .into(),
+                vertical_rule(2).into(),
+                iced::widget::slider(MIN_RATE..=MAX_RATE, app.settings.rate, |new_value| {
+                    if new_value != app.settings.rate {
+                        println!("Setting s.rate to {:?}", new_value);
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.rate = new_value
+                        }))
+                    } else {
+                        Message::SettingChanged(Arc::new(move |_: &mut Settings| {}))
+                    }
+                })
+                .step(5u16)
                 .into(),
             ])
             .into(),
             // Pitch slider
             row([
-                widget::text(app.settings.pitch)
+                widget::text(format!("Pitch {:.0}%", app.settings.pitch * 100.))
                     .width(70)
                     .horizontal_alignment(Horizontal::Right)
                     .into(),
                 vertical_rule(2).into(),
-                iced::widget::slider(
-                    VoicePitch::Soprano..=VoicePitch::Bass,
-                    app.settings.pitch,
-                    |new_value| {
-                        if new_value != app.settings.pitch {
-                            println!("Setting s.pitch to {:?}", new_value);
-                            Message::SettingChanged(Arc::new(move |s: &mut Settings| {
-                                s.pitch = new_value
-                            }))
-                        } else {
-                            Message::SettingChanged(Arc::new(move |_: &mut Settings| {}))
-                        }
-                    },
-                )
+                iced::widget::slider(0.0..=1.0, app.settings.pitch, |new_value| {
+                    if new_value != app.settings.pitch {
+                        println!("Setting s.pitch to {:?}", new_value);
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.pitch = new_value
+                        }))
+                    } else {
+                        Message::SettingChanged(Arc::new(move |_: &mut Settings| {}))
+                    }
+                })
+                .step(0.05)
                 .into(),
             ])
             .into(),
@@ -534,35 +2036,241 @@ fn settings_widget(app: &IcedApp) -> Element<'_, Message> {
                     .into(),
             ])
             .into(),
-            // Voice picker
+            // Selection look
+            row([
+                widget::text(format!("Line {}px", app.settings.rect_thickness))
+                    .width(70)
+                    .horizontal_alignment(Horizontal::Right)
+                    .into(),
+                vertical_rule(2).into(),
+                iced::widget::slider(1..=10, app.settings.rect_thickness, |new_value| {
+                    Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                        s.rect_thickness = new_value
+                    }))
+                })
+                .into(),
+            ])
+            .into(),
+            row([
+                widget::text("Fill")
+                    .width(70)
+                    .horizontal_alignment(Horizontal::Right)
+                    .into(),
+                vertical_rule(2).into(),
+                iced::widget::slider(0..=255, app.settings.rect_fill, |new_value| {
+                    Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                        s.rect_fill = new_value
+                    }))
+                })
+                .into(),
+            ])
+            .into(),
+            row([
+                widget::text("Dim outside")
+                    .width(70)
+                    .horizontal_alignment(Horizontal::Right)
+                    .into(),
+                vertical_rule(2).into(),
+                iced::widget::slider(0..=255, app.settings.dim_outside, |new_value| {
+                    Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                        s.dim_outside = new_value
+                    }))
+                })
+                .into(),
+            ])
+            .into(),
+            row([
+                widget::checkbox("Magnifier", app.settings.loupe)
+                    .on_toggle(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.loupe = new_value
+                        }))
+                    })
+                    .into(),
+                widget::checkbox("Show size", app.settings.size_readout)
+                    .on_toggle(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.size_readout = new_value
+                        }))
+                    })
+                    .into(),
+            ])
+            .spacing(5)
+            .into(),
+            // Magnifier filters
+            row([
+                widget::checkbox("Invert", app.settings.magnifier_invert)
+                    .on_toggle(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.magnifier_invert = new_value
+                        }))
+                    })
+                    .into(),
+                widget::checkbox("High contrast", app.settings.magnifier_high_contrast)
+                    .on_toggle(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.magnifier_high_contrast = new_value
+                        }))
+                    })
+                    .into(),
+            ])
+            .spacing(5)
+            .into(),
+            // Reader panel
+            row([
+                widget::text(format!("Text {}pt", app.settings.reader_font_size))
+                    .width(70)
+                    .horizontal_alignment(Horizontal::Right)
+                    .into(),
+                vertical_rule(2).into(),
+                iced::widget::slider(12..=96, app.settings.reader_font_size, |new_value| {
+                    Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                        s.reader_font_size = new_value
+                    }))
+                })
+                .step(2u16)
+                .into(),
+            ])
+            .into(),
+            row([
+                widget::text(format!("Spacing {:.1}", app.settings.reader_line_spacing))
+                    .width(70)
+                    .horizontal_alignment(Horizontal::Right)
+                    .into(),
+                vertical_rule(2).into(),
+                iced::widget::slider(1.0..=3.0, app.settings.reader_line_spacing, |new_value| {
+                    Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                        s.reader_line_spacing = new_value
+                    }))
+                })
+                .step(0.1)
+                .into(),
+            ])
+            .into(),
             row([iced::widget::pick_list(
-                app.tts
-                    .voices()
-                    .unwrap()
-                    .into_iter()
-                    .map(|v| PickableVoice(v))
-                    .collect::<Vec<_>>(),
-                Some(PickableVoice(
+                ReaderColours::ALL,
+                Some(app.settings.reader_colours),
+                |new_value| {
+                    println!("Setting s.reader_colours to {:?}", new_value);
+                    Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                        s.reader_colours = new_value
+                    }))
+                },
+            )
+            .width(200)
+            .into()])
+            .into(),
+            // Voice picker
+            row([
+                iced::widget::pick_list(
                     app.tts
                         .voices()
                         .unwrap()
                         .into_iter()
-                        .find(|v| v.id() == app.settings.voice)
-                        .unwrap_or(app.tts.voices().unwrap()[0].clone()),
-                )),
-                |new_value| {
-                    if new_value.0.id() != app.settings.voice {
-                        println!("Setting s.voice to {:?}", new_value);
+                        .map(|v| PickableVoice(v))
+                        .collect::<Vec<_>>(),
+                    Some(PickableVoice(
+                        app.tts
+                            .voices()
+                            .unwrap()
+                            .into_iter()
+                            .find(|v| v.id() == app.settings.voice)
+                            .unwrap_or(app.tts.voices().unwrap()[0].clone()),
+                    )),
+                    |new_value| {
+                        if new_value.0.id() != app.settings.voice {
+                            println!("Setting s.voice to {:?}", new_value);
+                            Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                                s.voice = new_value.0.id()
+                            }))
+                        } else {
+                            Message::SettingChanged(Arc::new(move |_: &mut Settings| {}))
+                        }
+                    },
+                )
+                .width(130)
+                .into(),
+                widget::button("Preview")
+                    .on_press(Message::PreviewVoice)
+                    .into(),
+            ])
+            .spacing(5)
+            .into(),
+            // Voices per language
+            row([
+                widget::checkbox("Detect language", app.settings.detect_language)
+                    .on_toggle(|new_value| {
                         Message::SettingChanged(Arc::new(move |s: &mut Settings| {
-                            s.voice = new_value.0.id()
+                            s.detect_language = new_value
                         }))
-                    } else {
-                        Message::SettingChanged(Arc::new(move |_: &mut Settings| {}))
-                    }
-                },
+                    })
+                    .into(),
+            ])
+            .into(),
+            column(
+                app.settings
+                    .language_voices
+                    .iter()
+                    .map(|(language, id)| language_voice_row(app, language.clone(), id)),
             )
-            .width(200)
-            .into()])
+            .into(),
+            row([
+                widget::text_input("Language", &app.language_voice_name)
+                    .on_input(Message::LanguageVoiceNameChanged)
+                    .width(120)
+                    .into(),
+                widget::button("Add voice")
+                    .on_press_maybe({
+                        let language = app.language_voice_name.trim().to_string();
+                        let voice = app.settings.voice.clone();
+                        (!language.is_empty()).then(|| {
+                            Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                                s.language_voices.insert(language.clone(), voice.clone());
+                            }))
+                        })
+                    })
+                    .into(),
+            ])
+            .into(),
+            // Translation
+            row([
+                widget::checkbox("Translate", app.settings.translate)
+                    .on_toggle(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.translate = new_value
+                        }))
+                    })
+                    .width(90)
+                    .into(),
+                widget::text_input("From", &app.settings.source_language)
+                    .on_input(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.source_language = new_value.clone()
+                        }))
+                    })
+                    .width(50)
+                    .into(),
+                widget::text_input("To", &app.settings.target_language)
+                    .on_input(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.target_language = new_value.clone()
+                        }))
+                    })
+                    .width(50)
+                    .into(),
+            ])
+            .spacing(5)
+            .into(),
+            row([
+                widget::text_input("Translation server", &app.settings.translation_endpoint)
+                    .on_input(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.translation_endpoint = new_value.clone()
+                        }))
+                    })
+                    .width(200)
+                    .into(),
+            ])
             .into(),
             // Detection model picker
             row([iced::widget::button(
@@ -594,21 +2302,123 @@ fn settings_widget(app: &IcedApp) -> Element<'_, Message> {
                 move |s: &mut Settings| {
                     if let Some(file) = FileDialog::new().add_filter("text", &["rten"]).pick_file()
                     {
-                        s.recognition_file = file;
+                        s.use_recognition_model(file);
                     }
                 },
             )))
             .into()])
             .into(),
-            // Position picker
-            row([iced::widget::mouse_area(iced::widget::text(
-                "Click & Drag here to move window(TODO)",
-            ))
-            .on_press(Message::DragWindow)
-            .on_release(Message::ReleaseWindow)
-            .on_exit(Message::ReleaseWindow)
-            .interaction(iced::mouse::Interaction::Grab)
-            .into()])
+            // Installed recognition models, tagged with the language they read
+            row([
+                iced::widget::pick_list(
+                    app.settings.recognition_models.clone(),
+                    app.settings
+                        .recognition_models
+                        .iter()
+                        .find(|m| m.file == app.settings.recognition_file)
+                        .cloned(),
+                    |new_value| {
+                        println!("Setting s.recognition_file to {:?}", new_value.file);
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.recognition_file = new_value.file.clone()
+                        }))
+                    },
+                )
+                .placeholder("Recognition model")
+                .width(125)
+                .into(),
+                widget::text_input(
+                    "Language",
+                    app.settings.recognition_language().unwrap_or_default(),
+                )
+                .on_input(|new_value| {
+                    Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                        s.set_recognition_language(new_value.clone())
+                    }))
+                })
+                .width(70)
+                .into(),
+            ])
+            .spacing(5)
+            .into(),
+            // Reading mode picker
+            row([
+                iced::widget::pick_list(
+                    ReadingMode::ALL,
+                    Some(app.settings.reading_mode),
+                    |new_value| {
+                        println!("Setting s.reading_mode to {:?}", new_value);
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.reading_mode = new_value
+                        }))
+                    },
+                )
+                .width(90)
+                .into(),
+                widget::checkbox("NATO", app.settings.phonetic_alphabet)
+                    .on_toggle(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.phonetic_alphabet = new_value
+                        }))
+                    })
+                    .into(),
+            ])
+            .into(),
+            row([
+                iced::widget::pick_list(
+                    SelectionShape::ALL,
+                    Some(app.settings.selection_shape),
+                    |new_value| {
+                        println!("Setting s.selection_shape to {:?}", new_value);
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.selection_shape = new_value
+                        }))
+                    },
+                )
+                .width(110)
+                .into(),
+                if app.settings.selection_shape.is_detected() {
+                    widget::checkbox("Hover", app.settings.hover_to_read)
+                        .on_toggle(|new_value| {
+                            Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                                s.hover_to_read = new_value
+                            }))
+                        })
+                        .into()
+                } else {
+                    widget::checkbox("Whole words", app.settings.lasso_whole_words)
+                        .on_toggle(|new_value| {
+                            Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                                s.lasso_whole_words = new_value
+                            }))
+                        })
+                        .into()
+                },
+            ])
+            .spacing(5)
+            .into(),
+            row([
+                widget::checkbox("Drag to select", app.settings.drag_draw)
+                    .on_toggle(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.drag_draw = new_value
+                        }))
+                    })
+                    .into(),
+                widget::checkbox("Read on release", app.settings.read_on_release)
+                    .on_toggle(|new_value| {
+                        Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                            s.read_on_release = new_value
+                        }))
+                    })
+                    .into(),
+            ])
+            .spacing(5)
+            .into(),
+            // Pronunciation editor
+            row([widget::button("Pronunciations")
+                .on_press(Message::Lexicon)
+                .into()])
             .into(),
         ])
         .into()
@@ -617,6 +2427,60 @@ fn settings_widget(app: &IcedApp) -> Element<'_, Message> {
     }
 }
 
+/// Handle for dragging the window around by
+fn grip() -> Element<'static, Message> {
+    tooltip(
+        mouse_area(
+            widget::container(text("≡").size(20))
+                .width(GRIP_WIDTH)
+                .height(WINDOW_SIZE.height)
+                .center_x()
+                .center_y(),
+        )
+        .on_press(Message::DragWindow)
+        .interaction(iced::mouse::Interaction::Grab),
+        "Drag to move",
+        tooltip::Position::FollowCursor,
+    )
+    .style(iced::theme::Container::Box)
+    .padding(5)
+    .into()
+}
+
+/// The voice used for `language`, with a button to go back to picking one automatically
+fn language_voice_row(app: &IcedApp, language: String, id: &str) -> Element<'static, Message> {
+    let voices = app.tts.voices().unwrap_or_default();
+    let selected = voices
+        .iter()
+        .find(|v| v.id() == id)
+        .cloned()
+        .map(PickableVoice);
+    let remove = language.clone();
+    row([
+        widget::text(language.clone()).width(40).into(),
+        iced::widget::pick_list(
+            voices.into_iter().map(PickableVoice).collect::<Vec<_>>(),
+            selected,
+            move |new_value| {
+                let language = language.clone();
+                Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                    s.language_voices.insert(language.clone(), new_value.0.id());
+                }))
+            },
+        )
+        .width(125)
+        .into(),
+        widget::button("✕")
+            .on_press(Message::SettingChanged(Arc::new(
+                move |s: &mut Settings| {
+                    s.language_voices.remove(&remove);
+                },
+            )))
+            .into(),
+    ])
+    .into()
+}
+
 #[derive(PartialEq, Clone, Debug)]
 struct PickableVoice(tts::Voice);
 impl Display for PickableVoice {
@@ -624,3 +2488,105 @@ impl Display for PickableVoice {
         f.write_str(&self.0.name())
     }
 }
+
+fn lexicon_widget(app: &IcedApp) -> Element<'_, Message> {
+    column([
+        // top rule
+        horizontal_rule(2).into(),
+        if app.settings.is_dirty() || app.lexicon_dirty {
+            row([
+                widget::button("CANCEL")
+                    .on_press(Message::SettingsCancel)
+                    .into(),
+                widget::button("APPLY")
+                    .on_press(Message::SettingsApply)
+                    .into(),
+            ])
+            .into()
+        } else {
+            horizontal_rule(0).into()
+        },
+        row([
+            widget::button("<").on_press(Message::Lexicon).into(),
+            widget::button("+")
+                .on_press(Message::LexiconChanged(Arc::new(|l: &mut Lexicon| {
+                    l.entries.push(LexiconEntry::default())
+                })))
+                .into(),
+            widget::button("Import")
+                .on_press(Message::LexiconImport)
+                .into(),
+            widget::button("Export")
+                .on_press(Message::LexiconExport)
+                .into(),
+        ])
+        .into(),
+        widget::checkbox("SSML phonemes", app.settings.lexicon_ssml)
+            .on_toggle(|new_value| {
+                Message::SettingChanged(Arc::new(move |s: &mut Settings| {
+                    s.lexicon_ssml = new_value
+                }))
+            })
+            .into(),
+        widget::scrollable(column(app.lexicon.entries.iter().enumerate().map(
+            |(i, entry)| {
+                column([
+                    horizontal_rule(2).into(),
+                    row([
+                        widget::text_input("Word", &entry.pattern)
+                            .on_input(move |new_value| {
+                                Message::LexiconChanged(Arc::new(move |l: &mut Lexicon| {
+                                    l.entries[i].pattern = new_value.clone()
+                                }))
+                            })
+                            .into(),
+                        widget::text_input("Say as", &entry.spoken)
+                            .on_input(move |new_value| {
+                                Message::LexiconChanged(Arc::new(move |l: &mut Lexicon| {
+                                    l.entries[i].spoken = new_value.clone()
+                                }))
+                            })
+                            .into(),
+                    ])
+                    .into(),
+                    row([
+                        widget::checkbox("Regex", entry.regex)
+                            .on_toggle(move |new_value| {
+                                Message::LexiconChanged(Arc::new(move |l: &mut Lexicon| {
+                                    l.entries[i].regex = new_value
+                                }))
+                            })
+                            .into(),
+                        widget::checkbox("Case", entry.case_sensitive)
+                            .on_toggle(move |new_value| {
+                                Message::LexiconChanged(Arc::new(move |l: &mut Lexicon| {
+                                    l.entries[i].case_sensitive = new_value
+                                }))
+                            })
+                            .into(),
+                        widget::text_input("IPA", entry.phoneme.as_deref().unwrap_or(""))
+                            .on_input(move |new_value| {
+                                Message::LexiconChanged(Arc::new(move |l: &mut Lexicon| {
+                                    l.entries[i].phoneme = if new_value.is_empty() {
+                                        None
+                                    } else {
+                                        Some(new_value.clone())
+                                    }
+                                }))
+                            })
+                            .into(),
+                        widget::button("X")
+                            .on_press(Message::LexiconChanged(Arc::new(move |l: &mut Lexicon| {
+                                l.entries.remove(i);
+                            })))
+                            .into(),
+                    ])
+                    .into(),
+                ])
+                .into()
+            },
+        )))
+        .into(),
+    ])
+    .into()
+}
@@ -0,0 +1,139 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+use whatlang::Lang;
+
+/// Guesses the language `text` is written in, as a code like "en" to match voices' language tags.
+/// Returns None when the text is too short or too ambiguous to tell
+pub fn detect(text: &str) -> Option<&'static str> {
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| language_code(info.lang()))
+}
+
+/// The two letter ISO 639-1 code voices are tagged with, for whatlang's three letter ISO 639-3 languages
+fn language_code(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Epo => "eo",
+        Lang::Eng => "en",
+        Lang::Rus => "ru",
+        Lang::Cmn => "zh",
+        Lang::Spa => "es",
+        Lang::Por => "pt",
+        Lang::Ita => "it",
+        Lang::Ben => "bn",
+        Lang::Fra => "fr",
+        Lang::Deu => "de",
+        Lang::Ukr => "uk",
+        Lang::Kat => "ka",
+        Lang::Ara => "ar",
+        Lang::Hin => "hi",
+        Lang::Jpn => "ja",
+        Lang::Heb => "he",
+        Lang::Yid => "yi",
+        Lang::Pol => "pl",
+        Lang::Amh => "am",
+        Lang::Jav => "jv",
+        Lang::Kor => "ko",
+        Lang::Nob => "nb",
+        Lang::Dan => "da",
+        Lang::Swe => "sv",
+        Lang::Fin => "fi",
+        Lang::Tur => "tr",
+        Lang::Nld => "nl",
+        Lang::Hun => "hu",
+        Lang::Ces => "cs",
+        Lang::Ell => "el",
+        Lang::Bul => "bg",
+        Lang::Bel => "be",
+        Lang::Mar => "mr",
+        Lang::Kan => "kn",
+        Lang::Ron => "ro",
+        Lang::Slv => "sl",
+        Lang::Hrv => "hr",
+        Lang::Srp => "sr",
+        Lang::Mkd => "mk",
+        Lang::Lit => "lt",
+        Lang::Lav => "lv",
+        Lang::Est => "et",
+        Lang::Tam => "ta",
+        Lang::Vie => "vi",
+        Lang::Urd => "ur",
+        Lang::Tha => "th",
+        Lang::Guj => "gu",
+        Lang::Uzb => "uz",
+        Lang::Pan => "pa",
+        Lang::Aze => "az",
+        Lang::Ind => "id",
+        Lang::Tel => "te",
+        Lang::Pes => "fa",
+        Lang::Mal => "ml",
+        Lang::Ori => "or",
+        Lang::Mya => "my",
+        Lang::Nep => "ne",
+        Lang::Sin => "si",
+        Lang::Khm => "km",
+        Lang::Tuk => "tk",
+        Lang::Aka => "ak",
+        Lang::Zul => "zu",
+        Lang::Sna => "sn",
+        Lang::Afr => "af",
+        Lang::Lat => "la",
+        Lang::Slk => "sk",
+        Lang::Cat => "ca",
+        Lang::Tgl => "tl",
+        Lang::Hye => "hy",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_clear_text() {
+        assert_eq!(
+            detect(
+                "Please remember to close the windows before you leave the house, \
+                 because the weather forecast says it will rain heavily tonight."
+            ),
+            Some("en")
+        );
+        assert_eq!(
+            detect("El rápido zorro marrón salta sobre el perro perezoso y corre hacia el bosque."),
+            Some("es")
+        );
+        assert_eq!(
+            detect("Der schnelle braune Fuchs springt über den faulen Hund und läuft in den Wald."),
+            Some("de")
+        );
+    }
+
+    #[test]
+    fn unreliable_guesses_are_dropped() {
+        for text in ["", "OK", "123 456", "Hi"] {
+            assert_eq!(detect(text), None, "{text:?}");
+        }
+        // Only whatlang's reliable guesses are used, whatever it guessed
+        for text in ["Pizza", "Hotel Europa", "The cat sat on the mat."] {
+            let reliable = whatlang::detect(text).is_some_and(|info| info.is_reliable());
+            assert_eq!(detect(text).is_some(), reliable, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn maps_to_two_letter_codes() {
+        assert_eq!(language_code(Lang::Eng), "en");
+        assert_eq!(language_code(Lang::Cmn), "zh");
+        assert_eq!(language_code(Lang::Pes), "fa");
+        assert_eq!(language_code(Lang::Nob), "nb");
+        assert_eq!(language_code(Lang::Heb), "he");
+        for &lang in Lang::all() {
+            let code = language_code(lang);
+            assert!(
+                code.len() == 2 && code.chars().all(|c| c.is_ascii_lowercase()),
+                "{lang:?} maps to {code:?}"
+            );
+        }
+    }
+}
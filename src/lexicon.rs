@@ -0,0 +1,270 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+use crate::options::get_config_file_path;
+use regex::Regex;
+use regex::RegexBuilder;
+use serde::*;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LexiconEntry {
+    /// Whole word (or phrase) to replace, or a regex if `regex` is set
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+
+    /// What the TTS should say instead
+    pub spoken: String,
+    /// Optional IPA pronunciation, only used when SSML output is enabled
+    #[serde(default)]
+    pub phoneme: Option<String>,
+}
+
+impl LexiconEntry {
+    fn to_regex(&self) -> Result<Regex, regex::Error> {
+        let pattern = if self.regex {
+            self.pattern.clone()
+        } else {
+            format!(r"\b{}\b", regex::escape(&self.pattern))
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+    }
+
+    fn replacement(&self, ssml: bool) -> Piece {
+        match &self.phoneme {
+            Some(phoneme) if ssml && !phoneme.is_empty() => Piece::Markup(format!(
+                "<phoneme alphabet=\"ipa\" ph=\"{}\">{}</phoneme>",
+                escape_xml(phoneme),
+                escape_xml(&self.spoken)
+            )),
+            _ => Piece::Text(self.spoken.clone()),
+        }
+    }
+
+    /// Splits `text` around each match of `re`, putting this entry's replacement in between
+    fn replace(&self, re: &Regex, text: String, ssml: bool) -> Vec<Piece> {
+        let mut pieces = vec![];
+        let mut last = 0;
+        for found in re.find_iter(&text) {
+            pieces.push(Piece::Text(text[last..found.start()].to_string()));
+            pieces.push(self.replacement(ssml));
+            last = found.end();
+        }
+        pieces.push(Piece::Text(text[last..].to_string()));
+        pieces
+    }
+}
+
+/// Part of the text being rewritten. Markup inserted by an entry is kept apart so later entries only match text
+enum Piece {
+    Text(String),
+    Markup(String),
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Lexicon {
+    pub entries: Vec<LexiconEntry>,
+}
+
+impl Lexicon {
+    /// Rewrites recognised text with the user's pronunciations, applying entries in order.
+    /// With `ssml` the result is a whole SSML document, with the text escaped
+    pub fn apply(&self, text: &str, ssml: bool) -> String {
+        let mut pieces = vec![Piece::Text(text.to_string())];
+        for entry in self.entries.iter().filter(|e| !e.pattern.is_empty()) {
+            match entry.to_regex() {
+                Ok(re) => {
+                    pieces = pieces
+                        .into_iter()
+                        .flat_map(|piece| match piece {
+                            Piece::Text(text) => entry.replace(&re, text, ssml),
+                            markup => vec![markup],
+                        })
+                        .collect();
+                }
+                Err(e) => {
+                    eprintln!("Skipping pronunciation {:?}: {:?}", entry.pattern, e);
+                }
+            }
+        }
+
+        let body = pieces
+            .into_iter()
+            .map(|piece| match piece {
+                Piece::Text(text) if ssml => escape_xml(&text),
+                Piece::Text(text) | Piece::Markup(text) => text,
+            })
+            .collect::<String>();
+        if ssml {
+            format!("<speak>{body}</speak>")
+        } else {
+            body
+        }
+    }
+
+    pub fn load() -> Self {
+        let lexicon_path = Lexicon::get_file_path();
+        match std::fs::read_to_string(&lexicon_path) {
+            Ok(lexicon_string) if lexicon_string.is_empty() => Self::default(),
+            Ok(lexicon_string) => serde_json::from_str(&lexicon_string).unwrap_or_else(|e| {
+                eprintln!("Error reading {:?}: {:?}", lexicon_path, e);
+                Self::default()
+            }),
+            Err(e) => {
+                eprintln!("Error reading {:?}: {:?}", lexicon_path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save_to_file(&self) -> Result<(), serde_json::Error> {
+        self.export(&Lexicon::get_file_path())
+    }
+
+    pub fn import(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string())
+    }
+
+    /// Writes to a temporary file next to `path` and renames it into place, like `Settings::save_to`,
+    /// so a crash or full disk can't leave a half written list
+    pub fn export(&self, path: &Path) -> Result<(), serde_json::Error> {
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+        let write = || -> Result<(), serde_json::Error> {
+            let file = File::create(&temp_path).map_err(serde_json::Error::io)?;
+            serde_json::to_writer_pretty(&file, self)?;
+            file.sync_all().map_err(serde_json::Error::io)?;
+            std::fs::rename(&temp_path, path).map_err(serde_json::Error::io)
+        };
+        write().inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp_path);
+        })
+    }
+
+    fn get_file_path() -> PathBuf {
+        get_config_file_path("pronunciation.json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pattern: &str, spoken: &str) -> LexiconEntry {
+        LexiconEntry {
+            pattern: pattern.to_string(),
+            spoken: spoken.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn lexicon(entries: Vec<LexiconEntry>) -> Lexicon {
+        Lexicon { entries }
+    }
+
+    #[test]
+    fn replaces_whole_words_ignoring_case() {
+        let lexicon = lexicon(vec![entry("SQL", "sequel")]);
+        assert_eq!(
+            lexicon.apply("sql and SQL, not SQLite", false),
+            "sequel and sequel, not SQLite"
+        );
+    }
+
+    #[test]
+    fn case_sensitive_entries_match_case() {
+        let lexicon = lexicon(vec![LexiconEntry {
+            case_sensitive: true,
+            ..entry("US", "United States")
+        }]);
+        assert_eq!(
+            lexicon.apply("us in the US", false),
+            "us in the United States"
+        );
+    }
+
+    #[test]
+    fn regex_entries_and_literal_dollars() {
+        let lexicon = lexicon(vec![LexiconEntry {
+            regex: true,
+            ..entry(r"\d+ USD", "$1 dollars")
+        }]);
+        assert_eq!(lexicon.apply("costs 5 USD", false), "costs $1 dollars");
+    }
+
+    #[test]
+    fn ssml_is_escaped_and_wrapped() {
+        let lexicon = lexicon(vec![LexiconEntry {
+            phoneme: Some("t\"o".to_string()),
+            ..entry("tomato", "to<ma>to")
+        }]);
+        assert_eq!(
+            lexicon.apply("A & B < tomato", true),
+            "<speak>A &amp; B &lt; \
+             <phoneme alphabet=\"ipa\" ph=\"t&quot;o\">to&lt;ma&gt;to</phoneme></speak>"
+        );
+        // Without SSML the phoneme isn't used and nothing is escaped
+        assert_eq!(lexicon.apply("A & tomato", false), "A & to<ma>to");
+    }
+
+    #[test]
+    fn later_entries_leave_inserted_markup_alone() {
+        let lexicon = lexicon(vec![
+            LexiconEntry {
+                phoneme: Some("ɡɪf".to_string()),
+                ..entry("gif", "gif")
+            },
+            entry("phoneme", "sound"),
+            entry("ipa", "beer"),
+        ]);
+        assert_eq!(
+            lexicon.apply("a gif phoneme", true),
+            "<speak>a <phoneme alphabet=\"ipa\" ph=\"ɡɪf\">gif</phoneme> sound</speak>"
+        );
+    }
+
+    #[test]
+    fn ssml_without_entries_is_still_a_document() {
+        assert_eq!(
+            Lexicon::default().apply("1 < 2", true),
+            "<speak>1 &lt; 2</speak>"
+        );
+    }
+
+    #[test]
+    fn export_replaces_the_file_whole() {
+        let dir = std::env::temp_dir().join(format!("draw-read-lexicon-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pronunciation.json");
+        std::fs::write(
+            &path,
+            "a much longer list than the one that replaces it ".repeat(20),
+        )
+        .unwrap();
+
+        let exported = lexicon(vec![entry("SQL", "sequel")]);
+        exported.export(&path).unwrap();
+        assert_eq!(Lexicon::import(&path).unwrap().entries, exported.entries);
+        // Only the list itself is left, not the temporary file it was written to
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+use crate::iced_logic::ScreenPoint;
+use crate::options::Settings;
+use iced::futures::channel::oneshot;
+use image::imageops;
+use image::imageops::FilterType;
+use image::RgbaImage;
+use xcap::Monitor;
+
+/// Size of the magnified view shown under the bar
+pub const LENS_SIZE: (u32, u32) = (400, 200);
+pub const MIN_ZOOM: f32 = 1.5;
+pub const MAX_ZOOM: f32 = 8.;
+/// Gap between the area being magnified and the window showing it
+const WINDOW_GAP: i32 = 16;
+
+/// What the magnifier last saw around the mouse
+#[derive(Clone)]
+pub struct Lens {
+    /// The captured area at its real size, for reading
+    pub captured: RgbaImage,
+    /// The captured area zoomed to `LENS_SIZE` with the colour filters applied
+    pub view: RgbaImage,
+}
+
+/// Runs [`capture`] on its own thread, as capturing a whole monitor is too slow to do between frames
+pub async fn capture_in_background(
+    centre: ScreenPoint,
+    settings: Settings,
+) -> Result<Lens, String> {
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(capture(centre, &settings));
+    });
+    receiver
+        .await
+        .unwrap_or_else(|_| Err("Capture stopped unexpectedly".to_string()))
+}
+
+/// Where to put a window of `size` beside the area [`capture`] takes around `centre`, so the window
+/// never shows up in its own lens. Below and to the right is preferred, staying inside `monitor`,
+/// given as `(left, top, right, bottom)`
+pub fn window_position(
+    centre: ScreenPoint,
+    zoom: f32,
+    size: (i32, i32),
+    monitor: (i32, i32, i32, i32),
+) -> ScreenPoint {
+    let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    // Captured in physical pixels, so this is never smaller than the captured area on high DPI screens
+    let half_width = (LENS_SIZE.0 as f32 / zoom / 2.).ceil() as i32 + WINDOW_GAP;
+    let half_height = (LENS_SIZE.1 as f32 / zoom / 2.).ceil() as i32 + WINDOW_GAP;
+    let (right, left) = (centre.x + half_width, centre.x - half_width - size.0);
+    let (below, above) = (centre.y + half_height, centre.y - half_height - size.1);
+
+    let (monitor_left, monitor_top, monitor_right, monitor_bottom) = monitor;
+    [(right, below), (left, below), (right, above), (left, above)]
+        .into_iter()
+        .map(|(x, y)| ScreenPoint { x, y })
+        .find(|p| {
+            p.x >= monitor_left
+                && p.y >= monitor_top
+                && p.x + size.0 <= monitor_right
+                && p.y + size.1 <= monitor_bottom
+        })
+        .unwrap_or(ScreenPoint {
+            x: right.min(monitor_right - size.0).max(monitor_left),
+            y: below.min(monitor_bottom - size.1).max(monitor_top),
+        })
+}
+
+/// Captures the screen around `centre` and magnifies it by the settings' zoom level
+pub fn capture(centre: ScreenPoint, settings: &Settings) -> Result<Lens, String> {
+    let monitor = Monitor::from_point(centre.x, centre.y).map_err(|e| e.to_string())?;
+    let screenshot = monitor.capture_image().map_err(|e| e.to_string())?;
+
+    let zoom = settings.magnifier_zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    let width = ((LENS_SIZE.0 as f32 / zoom) as u32).min(screenshot.width());
+    let height = ((LENS_SIZE.1 as f32 / zoom) as u32).min(screenshot.height());
+    // The mouse position is scaled down for high DPI screens, the screenshot isn't
+    let x = (centre.x as f32 * monitor.scale_factor()) as i32 - monitor.x();
+    let y = (centre.y as f32 * monitor.scale_factor()) as i32 - monitor.y();
+    let left = (x - width as i32 / 2).clamp(0, (screenshot.width() - width) as i32) as u32;
+    let top = (y - height as i32 / 2).clamp(0, (screenshot.height() - height) as i32) as u32;
+
+    let captured = imageops::crop_imm(&screenshot, left, top, width, height).to_image();
+    let mut view = imageops::resize(&captured, LENS_SIZE.0, LENS_SIZE.1, FilterType::Nearest);
+    apply_filters(&mut view, settings);
+
+    Ok(Lens { captured, view })
+}
+
+/// Inverts the colours and/or reduces the image to black and white, as the settings ask
+fn apply_filters(image: &mut RgbaImage, settings: &Settings) {
+    if !settings.magnifier_invert && !settings.magnifier_high_contrast {
+        return;
+    }
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let [r, g, b] = if settings.magnifier_high_contrast {
+            let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) as u8;
+            let level = if luma < 128 { 0 } else { 255 };
+            [level; 3]
+        } else {
+            [r, g, b]
+        };
+        pixel.0 = if settings.magnifier_invert {
+            [255 - r, 255 - g, 255 - b, a]
+        } else {
+            [r, g, b, a]
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: (i32, i32, i32, i32) = (0, 0, 1920, 1080);
+    const SIZE: (i32, i32) = (400, 250);
+
+    /// The area captured around `centre` at `zoom`, as `(left, top, right, bottom)`
+    fn captured_area(centre: ScreenPoint, zoom: f32) -> (i32, i32, i32, i32) {
+        let (half_width, half_height) = (
+            (LENS_SIZE.0 as f32 / zoom / 2.) as i32,
+            (LENS_SIZE.1 as f32 / zoom / 2.) as i32,
+        );
+        (
+            centre.x - half_width,
+            centre.y - half_height,
+            centre.x + half_width,
+            centre.y + half_height,
+        )
+    }
+
+    fn overlaps(window: ScreenPoint, (left, top, right, bottom): (i32, i32, i32, i32)) -> bool {
+        window.x < right && window.x + SIZE.0 > left && window.y < bottom && window.y + SIZE.1 > top
+    }
+
+    #[test]
+    fn window_sits_beside_the_lens_without_covering_it() {
+        for centre in [
+            ScreenPoint { x: 500, y: 300 },
+            ScreenPoint { x: 1900, y: 300 },
+            ScreenPoint { x: 500, y: 1070 },
+            ScreenPoint { x: 1910, y: 1070 },
+        ] {
+            for zoom in [MIN_ZOOM, 4., MAX_ZOOM] {
+                let window = window_position(centre, zoom, SIZE, MONITOR);
+                assert!(
+                    !overlaps(window, captured_area(centre, zoom)),
+                    "{centre} {zoom}"
+                );
+                assert!(
+                    window.x >= 0 && window.x + SIZE.0 <= 1920,
+                    "{centre} {zoom}"
+                );
+                assert!(
+                    window.y >= 0 && window.y + SIZE.1 <= 1080,
+                    "{centre} {zoom}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn window_prefers_below_right() {
+        let centre = ScreenPoint { x: 500, y: 300 };
+        let window = window_position(centre, 2., SIZE, MONITOR);
+        assert!(window.x > centre.x && window.y > centre.y);
+    }
+}
@@ -8,13 +8,35 @@ use iced::Error;
 use iced::Size;
 
 use crate::iced_ui::IcedApp;
+use crate::overrides::Overrides;
 use iced::Settings;
 
 mod iced_logic;
 mod iced_ui;
+mod language;
+mod lexicon;
+mod magnifier;
 mod options;
+mod overlay;
+mod overrides;
+mod selection;
+mod settings_state;
+mod speech;
+mod translate;
 
 fn main() -> Result<(), Error> {
+    if std::env::args().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", overrides::usage());
+        return Ok(());
+    }
+    match Overrides::parse(std::env::args().skip(1), std::env::vars_os()) {
+        Ok(overrides) => overrides::init(overrides),
+        Err(e) => {
+            eprintln!("{e}\nRun with --help to see the available settings");
+            std::process::exit(2);
+        }
+    }
+
     println!("Creating UI");
 
     let mut settings: iced::Settings<()> = Settings::default();
@@ -2,229 +2,855 @@
 // Copyright (C) 2024 agaeki
 
 use crate::iced_logic::ScreenPoint;
+use crate::overrides;
 use directories::ProjectDirs;
-use num_traits::cast::FromPrimitive;
+use iced::Color;
 use serde::*;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::fs::File;
-use std::io::BufReader;
+use std::path::Path;
 use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Copy, PartialOrd, PartialEq)]
-pub enum VoicePitch {
-    Soprano,
-    Mezzo,
-    #[default]
-    Alto,
-    Tenor,
-    Baritone,
-    Bass,
-}
+/// Slowest speech rate offered as a percentage of the voice's normal rate, mapped to the backend's minimum rate
+pub const MIN_RATE: u16 = 50;
+/// The voice's normal rate
+pub const NORMAL_RATE: u16 = 100;
+/// Fastest speech rate offered as a percentage of the voice's normal rate, mapped to the backend's maximum rate
+pub const MAX_RATE: u16 = 250;
 
-impl From<u8> for VoicePitch {
-    fn from(num: u8) -> Self {
-        match num {
-            1 => VoicePitch::Soprano,
-            2 => VoicePitch::Mezzo,
-            3 => VoicePitch::Alto,
-            4 => VoicePitch::Tenor,
-            5 => VoicePitch::Baritone,
-            _ => VoicePitch::Bass,
-        }
+/// Pitch from 0 (lowest) to 1 (highest), 0.5 is the voice's normal pitch
+pub const NORMAL_PITCH: f32 = 0.5;
+
+/// Bump this and add a step to `MIGRATIONS` whenever a change would misread older settings files
+pub const SETTINGS_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades a version `n` settings file to version `n + 1`
+const MIGRATIONS: [fn(&mut Value); SETTINGS_VERSION as usize] =
+    [migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// Version 1 rates were labelled words per minute, mapped from these onto the backend's minimum,
+/// normal and maximum rates
+const V1_MIN_WPM: u16 = 80;
+const V1_NORMAL_WPM: u16 = 180;
+const V1_MAX_WPM: u16 = 450;
+
+/// Version 0 files have no version field and store pitch and rate as the names of six fixed steps
+fn migrate_v0_to_v1(settings: &mut Value) {
+    // The old pitch steps were spaced a sixth of the backend's pitch range apart. Pitch is now scaled
+    // either side of the backend's normal pitch, so they only land where they were on backends whose
+    // normal pitch is the middle of their range, elsewhere they keep their order but move a little
+    if let Some(step) = settings["pitch"].as_str() {
+        let pitch = match step {
+            "Soprano" => 1. / 6.,
+            "Mezzo" => 2. / 6.,
+            "Tenor" => 4. / 6.,
+            "Baritone" => 5. / 6.,
+            "Bass" => 1.,
+            _ => NORMAL_PITCH,
+        };
+        settings["pitch"] = pitch.into();
+    }
+    // Fast used to sound the same as Default, it now sits between Default and Fastest
+    if let Some(step) = settings["rate"].as_str() {
+        let rate = match step {
+            "Slowest" => V1_MIN_WPM,
+            "Slow" => (V1_MIN_WPM + V1_NORMAL_WPM) / 2,
+            "Fast" => V1_NORMAL_WPM + (V1_MAX_WPM - V1_NORMAL_WPM) / 6,
+            "Fastest" => V1_NORMAL_WPM + (V1_MAX_WPM - V1_NORMAL_WPM) / 3,
+            "TooFast" => V1_NORMAL_WPM + (V1_MAX_WPM - V1_NORMAL_WPM) * 2 / 3,
+            _ => V1_NORMAL_WPM,
+        };
+        settings["rate"] = rate.into();
     }
 }
 
-impl Into<f32> for VoicePitch {
-    fn into(self) -> f32 {
-        match self {
-            VoicePitch::Soprano => 1.,
-            VoicePitch::Mezzo => 2.,
-            VoicePitch::Alto => 3.,
-            VoicePitch::Tenor => 4.,
-            VoicePitch::Baritone => 5.,
-            VoicePitch::Bass => 6.,
+/// Version 1 rates weren't really words per minute, as backends differ in how fast their normal rate is.
+/// They become a percentage of the normal rate, at the same place in the backend's range so voices sound the same
+fn migrate_v1_to_v2(settings: &mut Value) {
+    let to_percent = |rate: &mut Value| {
+        if let Some(wpm) = rate.as_f64() {
+            let percent = if wpm < V1_NORMAL_WPM as f64 {
+                MIN_RATE as f64
+                    + (wpm - V1_MIN_WPM as f64) / (V1_NORMAL_WPM - V1_MIN_WPM) as f64
+                        * (NORMAL_RATE - MIN_RATE) as f64
+            } else {
+                NORMAL_RATE as f64
+                    + (wpm - V1_NORMAL_WPM as f64) / (V1_MAX_WPM - V1_NORMAL_WPM) as f64
+                        * (MAX_RATE - NORMAL_RATE) as f64
+            };
+            *rate = (percent.round().max(0.) as u16).into();
+        }
+    };
+    to_percent(&mut settings["rate"]);
+    if let Some(profiles) = settings["profiles"].as_array_mut() {
+        for profile in profiles {
+            to_percent(&mut profile["rate"]);
         }
-    }
-}
-impl Into<f64> for VoicePitch {
-    fn into(self) -> f64 {
-        Into::<f32>::into(self) as f64
     }
 }
 
-impl FromPrimitive for VoicePitch {
-    fn from_i64(num: i64) -> std::option::Option<Self> {
-        match num {
-            1 => Some(VoicePitch::Soprano),
-            2 => Some(VoicePitch::Mezzo),
-            3 => Some(VoicePitch::Alto),
-            4 => Some(VoicePitch::Tenor),
-            5 => Some(VoicePitch::Baritone),
-            _ => Some(VoicePitch::Bass),
-        }
+/// Runs every migration needed to bring `settings` up to `SETTINGS_VERSION`, returning whether anything ran
+fn migrate(settings: &mut Value) -> bool {
+    let version = settings["version"].as_u64().unwrap_or(0) as usize;
+    if version > SETTINGS_VERSION as usize {
+        eprintln!("Settings are from a newer version ({version}), unknown fields will be dropped");
     }
-    fn from_u64(num: u64) -> std::option::Option<Self> {
-        match num {
-            1 => Some(VoicePitch::Soprano),
-            2 => Some(VoicePitch::Mezzo),
-            3 => Some(VoicePitch::Alto),
-            4 => Some(VoicePitch::Tenor),
-            5 => Some(VoicePitch::Baritone),
-            _ => Some(VoicePitch::Bass),
-        }
+    for migration in MIGRATIONS.iter().skip(version) {
+        migration(settings);
     }
+    settings["version"] = SETTINGS_VERSION.into();
+    version < SETTINGS_VERSION as usize
 }
 
-impl Display for VoicePitch {
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Copy, PartialEq, Eq)]
+pub enum ReadingMode {
+    #[default]
+    Words,
+    Spell,
+}
+
+impl ReadingMode {
+    pub const ALL: [ReadingMode; 2] = [ReadingMode::Words, ReadingMode::Spell];
+}
+
+impl Display for ReadingMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.write_str(&format!("{:?}", self).to_owned())
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Copy, PartialOrd, PartialEq)]
-pub enum VoiceRate {
-    Slowest,
-    Slow,
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Copy, PartialEq, Eq)]
+pub enum SelectionShape {
     #[default]
-    Default,
-    Fast,
-    Fastest,
-    TooFast,
+    Rectangle,
+    /// Freehand when dragging, or a polygon clicked out point by point without `drag_draw`
+    Lasso,
+    /// Outline every word found on screen and read the one clicked
+    Words,
+    /// Outline every line of text found on screen and read the one clicked
+    Lines,
 }
 
-impl From<u8> for VoiceRate {
-    fn from(num: u8) -> Self {
-        match num {
-            1 => VoiceRate::Slowest,
-            2 => VoiceRate::Slow,
-            3 => VoiceRate::Default,
-            4 => VoiceRate::Fast,
-            5 => VoiceRate::Fastest,
-            _ => VoiceRate::TooFast,
-        }
+impl SelectionShape {
+    pub const ALL: [SelectionShape; 4] = [
+        SelectionShape::Rectangle,
+        SelectionShape::Lasso,
+        SelectionShape::Words,
+        SelectionShape::Lines,
+    ];
+
+    /// Whether text is found up front and picked rather than selected by hand
+    pub fn is_detected(&self) -> bool {
+        matches!(self, SelectionShape::Words | SelectionShape::Lines)
+    }
+}
+
+impl Display for SelectionShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.write_str(match self {
+            SelectionShape::Rectangle => "Rectangle",
+            SelectionShape::Lasso => "Lasso",
+            SelectionShape::Words => "Pick a word",
+            SelectionShape::Lines => "Pick a line",
+        })
     }
 }
 
-impl Into<f32> for VoiceRate {
-    fn into(self) -> f32 {
+/// Text, background and highlight colours for the reader panel
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Copy, PartialEq, Eq)]
+pub enum ReaderColours {
+    #[default]
+    BlackOnWhite,
+    WhiteOnBlack,
+    YellowOnBlack,
+    BlackOnCream,
+}
+
+impl ReaderColours {
+    pub const ALL: [ReaderColours; 4] = [
+        ReaderColours::BlackOnWhite,
+        ReaderColours::WhiteOnBlack,
+        ReaderColours::YellowOnBlack,
+        ReaderColours::BlackOnCream,
+    ];
+
+    /// The text, background and highlight colours
+    pub fn colours(&self) -> (Color, Color, Color) {
         match self {
-            VoiceRate::Slowest => 1.,
-            VoiceRate::Slow => 2.,
-            VoiceRate::Default => 3.,
-            VoiceRate::Fast => 4.,
-            VoiceRate::Fastest => 5.,
-            VoiceRate::TooFast => 6.,
+            ReaderColours::BlackOnWhite => {
+                (Color::BLACK, Color::WHITE, Color::from_rgb8(255, 230, 0))
+            }
+            ReaderColours::WhiteOnBlack => {
+                (Color::WHITE, Color::BLACK, Color::from_rgb8(0, 90, 200))
+            }
+            ReaderColours::YellowOnBlack => (
+                Color::from_rgb8(255, 230, 0),
+                Color::BLACK,
+                Color::from_rgb8(0, 90, 200),
+            ),
+            ReaderColours::BlackOnCream => (
+                Color::BLACK,
+                Color::from_rgb8(250, 240, 210),
+                Color::from_rgb8(120, 200, 255),
+            ),
         }
     }
 }
-impl Into<f64> for VoiceRate {
-    fn into(self) -> f64 {
-        Into::<f32>::into(self) as f64
+
+impl Display for ReaderColours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.write_str(match self {
+            ReaderColours::BlackOnWhite => "Black on white",
+            ReaderColours::WhiteOnBlack => "White on black",
+            ReaderColours::YellowOnBlack => "Yellow on black",
+            ReaderColours::BlackOnCream => "Black on cream",
+        })
     }
 }
 
-impl FromPrimitive for VoiceRate {
-    fn from_i64(num: i64) -> std::option::Option<Self> {
-        match num {
-            1 => Some(VoiceRate::Slowest),
-            2 => Some(VoiceRate::Slow),
-            3 => Some(VoiceRate::Default),
-            4 => Some(VoiceRate::Fast),
-            5 => Some(VoiceRate::Fastest),
-            _ => Some(VoiceRate::TooFast),
+/// An installed recognition model and the language it reads, used to pick a matching voice
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecognitionModel {
+    /// Language code such as "en" or "ru", empty if unknown
+    pub language: String,
+    pub file: PathBuf,
+}
+
+impl Display for RecognitionModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let file_name = self.file.file_name().unwrap_or(self.file.as_os_str());
+        if self.language.is_empty() {
+            write!(f, "{}", file_name.to_string_lossy())
+        } else {
+            write!(f, "{}: {}", self.language, file_name.to_string_lossy())
         }
     }
-    fn from_u64(num: u64) -> std::option::Option<Self> {
-        match num {
-            1 => Some(VoiceRate::Slowest),
-            2 => Some(VoiceRate::Slow),
-            3 => Some(VoiceRate::Default),
-            4 => Some(VoiceRate::Fast),
-            5 => Some(VoiceRate::Fastest),
-            _ => Some(VoiceRate::TooFast),
+}
+
+/// A named copy of the voice, colour and model settings, so users can switch between them quickly
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Profile {
+    pub name: String,
+
+    pub detection_file: PathBuf,
+    pub recognition_file: PathBuf,
+
+    pub rect_colour: [u8; 4],
+
+    pub volume: u8,
+    pub pitch: f32,
+    pub rate: u16,
+    pub voice: String,
+}
+
+impl Profile {
+    pub fn from_settings(name: &str, settings: &Settings) -> Self {
+        Self {
+            name: name.to_string(),
+            detection_file: settings.detection_file.clone(),
+            recognition_file: settings.recognition_file.clone(),
+            rect_colour: settings.rect_colour,
+            volume: settings.volume,
+            pitch: settings.pitch,
+            rate: settings.rate,
+            voice: settings.voice.clone(),
         }
     }
-}
 
-impl Display for VoiceRate {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        f.write_str(&format!("{:?}", self).to_owned())
+    pub fn apply_to(&self, settings: &mut Settings) {
+        settings.detection_file = self.detection_file.clone();
+        settings.recognition_file = self.recognition_file.clone();
+        settings.rect_colour = self.rect_colour;
+        settings.volume = self.volume;
+        settings.pitch = self.pitch;
+        settings.rate = self.rate;
+        settings.voice = self.voice.clone();
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default = "Settings::initial")]
 pub struct Settings {
+    pub version: u32,
+
     pub detection_file: PathBuf,
     pub recognition_file: PathBuf,
+    /// Recognition models to choose between, `recognition_file` is the one in use
+    pub recognition_models: Vec<RecognitionModel>,
 
     pub rect_colour: [u8; 4],
+    /// Width of the selection outline, in pixels
+    pub rect_thickness: u8,
+    /// Opacity of `rect_colour` filling the selection, 0 for no fill
+    pub rect_fill: u8,
+    /// How much to darken the screen outside the selection, 0 for not at all
+    pub dim_outside: u8,
+    /// Show a magnified view of the screen beside the mouse while selecting
+    pub loupe: bool,
+    /// Show the selection's width and height in pixels
+    pub size_readout: bool,
+
+    /// How many times bigger the magnifier shows the screen around the mouse
+    pub magnifier_zoom: f32,
+    pub magnifier_invert: bool,
+    /// Show the magnifier in black and white only
+    pub magnifier_high_contrast: bool,
+
+    /// Size of the panel showing the text that was read
+    pub reader_width: u16,
+    pub reader_height: u16,
+    pub reader_font_size: u16,
+    pub reader_colours: ReaderColours,
+    /// Height of each line of the reader panel's text, relative to the font size
+    pub reader_line_spacing: f32,
 
     pub volume: u8,
-    pub pitch: VoicePitch,
-    pub rate: VoiceRate,
+    pub pitch: f32,
+    /// Speaking rate as a percentage of the voice's normal rate, between `MIN_RATE` and `MAX_RATE`
+    pub rate: u16,
     pub voice: String,
 
+    /// Guess the language of each line read and use a voice for it
+    pub detect_language: bool,
+    /// Voice ids to use for particular languages, instead of the first installed voice for them
+    pub language_voices: BTreeMap<String, String>,
+
+    /// Translate recognised text with a local server before reading it
+    pub translate: bool,
+    /// A LibreTranslate compatible `/translate` URL
+    pub translation_endpoint: String,
+    /// Language code of the text on screen, or "auto" to let the server guess
+    pub source_language: String,
+    /// Language code to translate into, a voice for it is used when one is installed
+    pub target_language: String,
+
     pub position: ScreenPoint,
 
+    /// Select by dragging, otherwise the first click sets one corner and the second click the opposite one
     pub drag_draw: bool,
+    /// Read as soon as the mouse is released instead of waiting for the selection to be adjusted and confirmed
+    pub read_on_release: bool,
+    pub selection_shape: SelectionShape,
+    /// Only read words that lie entirely inside a lasso, rather than whatever is left showing after masking
+    pub lasso_whole_words: bool,
+    /// Read a picked word or line by resting the mouse on it, as well as by clicking
+    pub hover_to_read: bool,
+
+    /// Wrap lexicon phonemes in SSML, only for engines that understand it
+    pub lexicon_ssml: bool,
+
+    /// Spelling can also be chosen per capture by holding shift when releasing the selection
+    pub reading_mode: ReadingMode,
+    pub phonetic_alphabet: bool,
+
+    pub profiles: Vec<Profile>,
+    /// The profile the settings above were last switched to, kept up to date when settings are applied
+    pub active_profile: Option<String>,
 }
 
 impl Settings {
-    pub fn save_to_file(&self) -> Result<(), serde_json::Error> {
-        let settings_path = Settings::get_file_path();
-        serde_json::to_writer_pretty(File::create(settings_path).unwrap(), self)
+    pub fn save_to_file(&self) -> Result<(), String> {
+        self.save_to(&Settings::get_file_path())
+    }
+
+    /// Writes to a temporary file next to `settings_path` and renames it into place, so a crash part way
+    /// through can't leave a half written file. A file from a newer version is left alone
+    fn save_to(&self, settings_path: &Path) -> Result<(), String> {
+        let file_version = std::fs::read_to_string(settings_path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+            .and_then(|v| v["version"].as_u64());
+        if let Some(version) = file_version
+            && version > SETTINGS_VERSION as u64
+        {
+            return Err(format!(
+                "{:?} is from a newer version ({version}), not overwriting it",
+                settings_path
+            ));
+        }
+
+        let temp_path = settings_path.with_extension("json.tmp");
+        let write = || -> std::io::Result<()> {
+            let file = File::create(&temp_path)?;
+            serde_json::to_writer_pretty(&file, self)?;
+            file.sync_all()?;
+            std::fs::rename(&temp_path, settings_path)
+        };
+        write().map_err(|e| format!("Error writing {:?}: {e}", settings_path))
     }
 
     fn get_file_path() -> PathBuf {
-        if let Some(proj_dirs) = ProjectDirs::from("net", "agaeki", "draw-read") {
-            let settings_path = proj_dirs.config_dir().join("settings.json");
-            match settings_path.try_exists() {
-                Ok(false) => {
-                    let _ = std::fs::create_dir_all(proj_dirs.config_dir());
-                    File::create(settings_path.clone()).unwrap();
-                }
-                Err(e) => {
-                    eprintln!("Error finding path {:?}: {:?}", settings_path, e);
-                }
-                _ => (),
-            }
-            return settings_path;
+        match overrides::get().and_then(|o| o.config_file.clone()) {
+            Some(config_file) => create_if_missing(config_file),
+            None => get_config_file_path("settings.json"),
         }
-        panic!("Error finding project dir for settings file!");
     }
-}
 
-impl Default for Settings {
-    fn default() -> Self {
-        let mut default_settings = Self {
+    /// Settings used for a fresh install, and for any field missing from the settings file
+    pub fn initial() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
             detection_file: "text-detection.rten".into(),
             recognition_file: "text-recognition.rten".into(),
+            recognition_models: vec![RecognitionModel {
+                language: String::new(),
+                file: "text-recognition.rten".into(),
+            }],
             rect_colour: [0, 255, 0, 255],
+            rect_thickness: 1,
+            rect_fill: 0,
+            dim_outside: 0,
+            loupe: false,
+            size_readout: false,
+            magnifier_zoom: 2.,
+            magnifier_invert: false,
+            magnifier_high_contrast: false,
+            reader_width: 500,
+            reader_height: 300,
+            reader_font_size: 28,
+            reader_colours: ReaderColours::default(),
+            reader_line_spacing: 1.5,
             volume: 255,
-            pitch: VoicePitch::default(),
-            rate: VoiceRate::default(),
+            pitch: NORMAL_PITCH,
+            rate: NORMAL_RATE,
             voice: String::default(),
+            detect_language: false,
+            language_voices: BTreeMap::new(),
+            translate: false,
+            translation_endpoint: "http://localhost:5000/translate".to_string(),
+            source_language: "auto".to_string(),
+            target_language: "en".to_string(),
             position: ScreenPoint::default(),
             drag_draw: true,
+            read_on_release: false,
+            selection_shape: SelectionShape::default(),
+            lasso_whole_words: false,
+            hover_to_read: false,
+            lexicon_ssml: false,
+            reading_mode: ReadingMode::default(),
+            phonetic_alphabet: false,
+            profiles: vec![],
+            active_profile: None,
+        }
+    }
+
+    /// The language of the recognition model in use, if it has been tagged with one
+    pub fn recognition_language(&self) -> Option<&str> {
+        self.recognition_models
+            .iter()
+            .find(|m| m.file == self.recognition_file)
+            .map(|m| m.language.as_str())
+            .filter(|language| !language.is_empty())
+    }
+
+    /// Uses `file` for recognition, adding it to the installed models if it's new
+    pub fn use_recognition_model(&mut self, file: PathBuf) {
+        if !self.recognition_models.iter().any(|m| m.file == file) {
+            self.recognition_models.push(RecognitionModel {
+                language: String::new(),
+                file: file.clone(),
+            });
+        }
+        self.recognition_file = file;
+    }
+
+    /// Tags the recognition model in use with `language`
+    pub fn set_recognition_language(&mut self, language: String) {
+        let file = self.recognition_file.clone();
+        self.use_recognition_model(file);
+        if let Some(model) = self
+            .recognition_models
+            .iter_mut()
+            .find(|m| m.file == self.recognition_file)
+        {
+            model.language = language;
+        }
+    }
+
+    /// Saves the current voice, colour and model settings as `name`, replacing any profile with that name
+    pub fn save_profile(&mut self, name: &str) {
+        let profile = Profile::from_settings(name, self);
+        match self.profiles.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+        self.active_profile = Some(name.to_string());
+    }
+
+    /// Applies the profile at `index` and makes it the active one, returning false if there isn't one
+    pub fn switch_profile(&mut self, index: usize) -> bool {
+        if let Some(profile) = self.profiles.get(index).cloned() {
+            profile.apply_to(self);
+            self.active_profile = Some(profile.name);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn delete_profile(&mut self, name: &str) {
+        self.profiles.retain(|p| p.name != name);
+        if self.active_profile.as_deref() == Some(name) {
+            self.active_profile = None;
+        }
+    }
+
+    pub fn active_profile_index(&self) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|p| Some(&p.name) == self.active_profile.as_ref())
+    }
+
+    /// Reads the settings file. Command line and environment overrides aren't included,
+    /// `SettingsState` layers them on top so they are never saved
+    pub fn load() -> Self {
+        Settings::load_from(&Settings::get_file_path())
+    }
+
+    /// Re-reads the settings file without changing it, reporting any problem instead of resetting
+    pub fn reload() -> Result<Self, String> {
+        Settings::reload_from(&Settings::get_file_path())
+    }
+
+    /// Reads a settings file that changed while running, the same way as [`Settings::load`] but without
+    /// backing it up or saving over it. A file that isn't a settings object at all, e.g. one half
+    /// written by an editor, is an error so the settings in use are kept
+    fn reload_from(settings_path: &Path) -> Result<Self, String> {
+        let settings_string = std::fs::read_to_string(settings_path)
+            .map_err(|e| format!("{:?}: {e}", settings_path))?;
+        let file_value = match serde_json::from_str::<Value>(&settings_string) {
+            Ok(file_value @ Value::Object(_)) => file_value,
+            Ok(_) => return Err(format!("{:?}: not a settings object", settings_path)),
+            Err(e) => return Err(format!("{:?}: {e}", settings_path)),
         };
+        let (settings, dropped_fields, _) = Settings::from_file_value(file_value);
+        if !dropped_fields.is_empty() {
+            eprintln!("Reset unreadable settings {:?} to defaults", dropped_fields);
+        }
+        Ok(settings)
+    }
+
+    pub fn file_modified_time() -> Option<SystemTime> {
+        std::fs::metadata(Settings::get_file_path())
+            .and_then(|m| m.modified())
+            .ok()
+    }
 
-        let settings_path = Settings::get_file_path();
-        let settings_string = std::fs::read_to_string(settings_path.clone()).unwrap();
+    /// Keeps values that can be edited by hand in the range the app supports
+    pub fn clamp_values(&mut self) {
+        self.pitch = self.pitch.clamp(0., 1.);
+        self.rate = self.rate.clamp(MIN_RATE, MAX_RATE);
+    }
 
-        match settings_string.len() {
-            0 => {
-                let _ = serde_json::to_writer_pretty(
-                    File::create(settings_path).unwrap(),
-                    &default_settings,
-                );
+    /// Reads a settings file, migrating it from older versions.
+    /// Anything that can't be read is reset to its default, after backing up the original file
+    fn load_from(settings_path: &Path) -> Self {
+        let settings_string = match std::fs::read_to_string(settings_path) {
+            Ok(settings_string) => settings_string,
+            Err(e) => {
+                eprintln!("Error reading {:?}: {:?}", settings_path, e);
+                return Settings::initial();
             }
+        };
+
+        if settings_string.trim().is_empty() {
+            let settings = Settings::initial();
+            let _ = settings.save_to(settings_path);
+            return settings;
+        }
+
+        let file_value = match serde_json::from_str::<Value>(&settings_string) {
+            Ok(file_value @ Value::Object(_)) => file_value,
             _ => {
-                default_settings =
-                    serde_json::from_reader(BufReader::new(File::open(settings_path).unwrap()))
-                        .unwrap();
+                eprintln!("Settings file is unreadable, resetting to defaults");
+                Settings::back_up(settings_path);
+                let settings = Settings::initial();
+                let _ = settings.save_to(settings_path);
+                return settings;
+            }
+        };
+        let (settings, dropped_fields, migrated) = Settings::from_file_value(file_value);
+        if !dropped_fields.is_empty() {
+            eprintln!("Reset unreadable settings {:?} to defaults", dropped_fields);
+            Settings::back_up(settings_path);
+        }
+
+        if (migrated || !dropped_fields.is_empty())
+            && let Err(e) = settings.save_to(settings_path)
+        {
+            eprintln!("{e}");
+        }
+        settings
+    }
+
+    /// Migrates and reads the contents of a settings file, keeping values in range.
+    /// Returns the settings, the fields reset to their defaults and whether it was migrated
+    fn from_file_value(mut file_value: Value) -> (Self, Vec<String>, bool) {
+        let migrated = migrate(&mut file_value);
+        let (mut settings, dropped_fields) = Settings::from_value_per_field(file_value);
+        settings.clamp_values();
+        (settings, dropped_fields, migrated)
+    }
+
+    /// Deserializes `file_value` one field at a time so a single bad value only loses that field.
+    /// Returns the settings and the fields that were dropped
+    fn from_value_per_field(file_value: Value) -> (Self, Vec<String>) {
+        if let Ok(settings) = serde_json::from_value(file_value.clone()) {
+            return (settings, vec![]);
+        }
+
+        let mut merged = Map::new();
+        let mut dropped_fields = vec![];
+        if let Value::Object(fields) = file_value {
+            for (key, value) in fields {
+                merged.insert(key.clone(), value);
+                if serde_json::from_value::<Settings>(Value::Object(merged.clone())).is_err() {
+                    merged.remove(&key);
+                    dropped_fields.push(key);
+                }
+            }
+        }
+        let settings = serde_json::from_value(Value::Object(merged)).unwrap_or_else(|e| {
+            eprintln!("Error reading settings: {:?}", e);
+            Settings::initial()
+        });
+        (settings, dropped_fields)
+    }
+
+    /// Copies the settings file next to itself before it gets overwritten with defaults
+    fn back_up(settings_path: &Path) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let backup_path = settings_path.with_extension(format!("{timestamp}.json.bak"));
+        match std::fs::copy(settings_path, &backup_path) {
+            Ok(_) => eprintln!("Backed up settings to {:?}", backup_path),
+            Err(e) => eprintln!("Error backing up settings to {:?}: {:?}", backup_path, e),
+        }
+    }
+}
+
+/// Finds `file_name` in the config dir, creating the dir and an empty file if they don't exist yet.
+/// When `--config` is given, other config files live next to that file instead
+pub fn get_config_file_path(file_name: &str) -> PathBuf {
+    if let Some(config_file) = overrides::get().and_then(|o| o.config_file.as_ref()) {
+        let config_dir = config_file.parent().unwrap_or(Path::new(""));
+        return create_if_missing(config_dir.join(file_name));
+    }
+    if let Some(proj_dirs) = ProjectDirs::from("net", "agaeki", "draw-read") {
+        return create_if_missing(proj_dirs.config_dir().join(file_name));
+    }
+    panic!("Error finding project dir for {file_name}!");
+}
+
+fn create_if_missing(file_path: PathBuf) -> PathBuf {
+    match file_path.try_exists() {
+        Ok(false) => {
+            if let Some(dir) = file_path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            if let Err(e) = File::create(&file_path) {
+                eprintln!("Error creating {:?}: {:?}", file_path, e);
             }
         }
+        Err(e) => {
+            eprintln!("Error finding path {:?}: {:?}", file_path, e);
+        }
+        _ => (),
+    }
+    file_path
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::load()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A settings file in a directory of its own, so tests don't touch the real settings or each other
+    fn settings_file(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("draw-read-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn migrates_v0_pitch_and_rate_names() {
+        let path = settings_file("v0", r#"{"pitch": "Bass", "rate": "Slow", "volume": 100}"#);
+        let settings = Settings::load_from(&path);
+        assert_eq!(settings.pitch, 1.);
+        // Half way between the slowest and normal rates, through version 1's words per minute
+        assert_eq!(settings.rate, (MIN_RATE + NORMAL_RATE) / 2);
+        assert_eq!(settings.volume, 100);
+
+        // The migrated file is saved so it only happens once
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["version"], SETTINGS_VERSION);
+        assert_eq!(saved["pitch"], 1.);
+    }
+
+    #[test]
+    fn migrates_v1_rates_to_percentages() {
+        let path = settings_file(
+            "v1",
+            r#"{"version": 1, "rate": 450, "profiles": [{"name": "Slow", "detection_file": "",
+                "recognition_file": "", "rect_colour": [0, 0, 0, 255], "volume": 255, "pitch": 0.5,
+                "rate": 80, "voice": ""}]}"#,
+        );
+        let settings = Settings::load_from(&path);
+        assert_eq!(settings.rate, MAX_RATE);
+        assert_eq!(settings.profiles[0].rate, MIN_RATE);
+
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["version"], SETTINGS_VERSION);
+        assert_eq!(saved["profiles"][0]["rate"], MIN_RATE);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let path = settings_file("missing", r#"{"version": 2, "volume": 100}"#);
+        let settings = Settings::load_from(&path);
+        assert_eq!(settings.volume, 100);
+        assert_eq!(settings.rate, NORMAL_RATE);
+        assert_eq!(settings.rect_colour, Settings::initial().rect_colour);
+    }
+
+    #[test]
+    fn one_bad_field_only_resets_that_field() {
+        let path = settings_file(
+            "bad-field",
+            r#"{"version": 2, "volume": "loud", "rate": 200, "voice": "Zira"}"#,
+        );
+        let settings = Settings::load_from(&path);
+        assert_eq!(settings.volume, Settings::initial().volume);
+        assert_eq!(settings.rate, 200);
+        assert_eq!(settings.voice, "Zira");
+    }
+
+    #[test]
+    fn non_object_json_resets_to_defaults() {
+        let path = settings_file("not-object", "[1, 2, 3]");
+        let settings = Settings::load_from(&path);
+        assert_eq!(settings, Settings::initial());
+
+        // The unreadable file is backed up before being replaced
+        let backups = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter(|f| {
+                f.as_ref()
+                    .unwrap()
+                    .path()
+                    .to_string_lossy()
+                    .ends_with(".bak")
+            })
+            .count();
+        assert_eq!(backups, 1);
+    }
+
+    #[test]
+    fn newer_version_is_read_but_not_overwritten() {
+        let contents = r#"{"version": 99, "volume": 100, "new_setting": true}"#;
+        let path = settings_file("newer", contents);
+        let settings = Settings::load_from(&path);
+        assert_eq!(settings.volume, 100);
+
+        assert!(settings.save_to(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+    }
+
+    #[test]
+    fn reload_recovers_fields_and_clamps_without_saving() {
+        let contents = r#"{"volume": 100, "rate": 5000, "rect_colour": "red"}"#;
+        let path = settings_file("reload", contents);
+        let settings = Settings::reload_from(&path).unwrap();
+        assert_eq!(settings.volume, 100);
+        assert_eq!(settings.rate, MAX_RATE);
+        assert_eq!(settings.rect_colour, Settings::initial().rect_colour);
+
+        // The file is the user's to fix, it's neither backed up nor migrated in place
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn reload_rejects_files_that_are_not_settings() {
+        for (name, contents) in [
+            ("reload-half", r#"{"volume": 1"#),
+            ("reload-list", "[1, 2]"),
+        ] {
+            let path = settings_file(name, contents);
+            assert!(Settings::reload_from(&path).is_err(), "{contents}");
+        }
+    }
+
+    #[test]
+    fn saving_a_profile_replaces_one_with_the_same_name() {
+        let mut settings = Settings::initial();
+        settings.rate = 200;
+        settings.save_profile("Reading");
+        settings.rate = 300;
+        settings.save_profile("Reading");
+
+        assert_eq!(settings.profiles.len(), 1);
+        assert_eq!(settings.profiles[0].rate, 300);
+        assert_eq!(settings.active_profile.as_deref(), Some("Reading"));
+    }
+
+    #[test]
+    fn switching_profile_applies_its_settings() {
+        let mut settings = Settings::initial();
+        settings.rate = 200;
+        settings.voice = "Zira".to_string();
+        settings.save_profile("Slow");
+        settings.rate = 400;
+        settings.voice = "David".to_string();
+        settings.save_profile("Fast");
+
+        assert!(settings.switch_profile(0));
+        assert_eq!(settings.rate, 200);
+        assert_eq!(settings.voice, "Zira");
+        assert_eq!(settings.active_profile_index(), Some(0));
+
+        assert!(!settings.switch_profile(2));
+        assert_eq!(settings.active_profile.as_deref(), Some("Slow"));
+    }
+
+    #[test]
+    fn deleting_the_active_profile_clears_it() {
+        let mut settings = Settings::initial();
+        settings.save_profile("Slow");
+        settings.save_profile("Fast");
+
+        settings.delete_profile("Slow");
+        assert_eq!(settings.active_profile.as_deref(), Some("Fast"));
+        settings.delete_profile("Fast");
+        assert!(settings.profiles.is_empty());
+        assert_eq!(settings.active_profile, None);
+    }
+
+    #[test]
+    fn save_replaces_the_file_whole() {
+        let path = settings_file("save", "{}");
+        let mut settings = Settings::initial();
+        settings.volume = 42;
+        settings.save_to(&path).unwrap();
 
-        default_settings
+        assert_eq!(Settings::load_from(&path).volume, 42);
+        assert!(!path.with_extension("json.tmp").exists());
     }
 }
@@ -0,0 +1,610 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+use crate::iced_logic::ImagePoint;
+use crate::options::Settings;
+use iced::advanced::image;
+use iced::advanced::image::Renderer as _;
+use iced::advanced::layout;
+use iced::advanced::renderer;
+use iced::advanced::text;
+use iced::advanced::widget::Tree;
+use iced::advanced::Layout;
+use iced::advanced::Renderer as _;
+use iced::advanced::Widget;
+use iced::alignment;
+use iced::mouse;
+use iced::widget::canvas;
+use iced::widget::image::Handle;
+use iced::Border;
+use iced::Color;
+use iced::Element;
+use iced::Length;
+use iced::Pixels;
+use iced::Point;
+use iced::Rectangle;
+use iced::Shadow;
+use iced::Size;
+
+/// Width of the squares drawn on the corners and edges of a selection, in image pixels
+pub const HANDLE_SIZE: f32 = 6.;
+/// Width and height of the magnified view around the mouse
+const LOUPE_SIZE: f32 = 160.;
+const LOUPE_ZOOM: f32 = 4.;
+/// Distance from the mouse to the magnified view, so it doesn't hide what is being selected
+const LOUPE_OFFSET: f32 = 24.;
+const READOUT_TEXT_SIZE: f32 = 14.;
+
+/// Something drawn over the screenshot, in image coordinates
+#[derive(Debug, Clone)]
+pub enum Shape {
+    /// An outline, with squares on the corners and edges if `handles` is set
+    Rectangle {
+        top_left: ImagePoint,
+        bottom_right: ImagePoint,
+        handles: bool,
+    },
+    /// The area that will be read, filled and with the rest of the screen dimmed as the settings ask
+    Selection {
+        top_left: ImagePoint,
+        bottom_right: ImagePoint,
+        handles: bool,
+    },
+    /// Straight lines joining each point to the next
+    Path(Vec<ImagePoint>),
+}
+
+/// How the selection is drawn, taken from the settings
+struct Style {
+    colour: Color,
+    thickness: f32,
+    fill: Color,
+    dim: Color,
+    loupe: bool,
+    size_readout: bool,
+}
+
+/// The screenshot with the selection drawn on top as a separate layer.
+/// The screenshot's handle is made once when it is taken, so moving the selection
+/// only redraws the shapes rather than copying and re-uploading the whole image
+pub struct Overlay {
+    screenshot: Handle,
+    size: Size,
+    style: Style,
+    shapes: Vec<Shape>,
+}
+
+impl Overlay {
+    pub fn new(
+        screenshot: Handle,
+        size: (u32, u32),
+        settings: &Settings,
+        shapes: Vec<Shape>,
+    ) -> Self {
+        let [r, g, b, a] = settings.rect_colour;
+        Self {
+            screenshot,
+            size: Size::new(size.0 as f32, size.1 as f32),
+            style: Style {
+                colour: Color::from_rgba8(r, g, b, a as f32 / 255.),
+                thickness: settings.rect_thickness.max(1) as f32,
+                fill: Color::from_rgba8(r, g, b, settings.rect_fill as f32 / 255.),
+                dim: Color::from_rgba8(0, 0, 0, settings.dim_outside as f32 / 255.),
+                loupe: settings.loupe,
+                size_readout: settings.size_readout,
+            },
+            shapes,
+        }
+    }
+
+    fn draw_outline<Renderer: renderer::Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        origin: Point,
+        top_left: ImagePoint,
+        bottom_right: ImagePoint,
+        handles: bool,
+    ) {
+        let (left, top) = (top_left.x as f32, top_left.y as f32);
+        let (right, bottom) = (bottom_right.x as f32, bottom_right.y as f32);
+        let thickness = self.style.thickness;
+        // The outline is drawn inside the selection so it never covers the text around it
+        let (width, height) = (right - left + 1., bottom - top + 1.);
+        fill(
+            renderer,
+            origin,
+            left,
+            top,
+            width,
+            thickness,
+            self.style.colour,
+        );
+        fill(
+            renderer,
+            origin,
+            left,
+            bottom + 1. - thickness,
+            width,
+            thickness,
+            self.style.colour,
+        );
+        fill(
+            renderer,
+            origin,
+            left,
+            top,
+            thickness,
+            height,
+            self.style.colour,
+        );
+        fill(
+            renderer,
+            origin,
+            right + 1. - thickness,
+            top,
+            thickness,
+            height,
+            self.style.colour,
+        );
+        if handles {
+            let (mid_x, mid_y) = ((left + right) / 2., (top + bottom) / 2.);
+            for (x, y) in [
+                (left, top),
+                (mid_x, top),
+                (right, top),
+                (right, mid_y),
+                (right, bottom),
+                (mid_x, bottom),
+                (left, bottom),
+                (left, mid_y),
+            ] {
+                fill(
+                    renderer,
+                    origin,
+                    x - HANDLE_SIZE / 2.,
+                    y - HANDLE_SIZE / 2.,
+                    HANDLE_SIZE,
+                    HANDLE_SIZE,
+                    self.style.colour,
+                );
+            }
+        }
+    }
+
+    /// Darkens everything around the selection and fills the selection itself
+    fn draw_selection_area<Renderer: renderer::Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        origin: Point,
+        top_left: ImagePoint,
+        bottom_right: ImagePoint,
+    ) {
+        let (left, top) = (top_left.x as f32, top_left.y as f32);
+        let (right, bottom) = (bottom_right.x as f32 + 1., bottom_right.y as f32 + 1.);
+        let (width, height) = (self.size.width, self.size.height);
+        if self.style.dim.a > 0. {
+            fill(renderer, origin, 0., 0., width, top, self.style.dim);
+            fill(
+                renderer,
+                origin,
+                0.,
+                bottom,
+                width,
+                height - bottom,
+                self.style.dim,
+            );
+            fill(
+                renderer,
+                origin,
+                0.,
+                top,
+                left,
+                bottom - top,
+                self.style.dim,
+            );
+            fill(
+                renderer,
+                origin,
+                right,
+                top,
+                width - right,
+                bottom - top,
+                self.style.dim,
+            );
+        }
+        if self.style.fill.a > 0. {
+            fill(
+                renderer,
+                origin,
+                left,
+                top,
+                right - left,
+                bottom - top,
+                self.style.fill,
+            );
+        }
+    }
+
+    /// Shows the selection's size just below it, or above it at the bottom of the screen
+    fn draw_size_readout<Renderer: text::Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        origin: Point,
+        top_left: ImagePoint,
+        bottom_right: ImagePoint,
+    ) {
+        let content = format!(
+            "{} × {}",
+            bottom_right.x - top_left.x + 1,
+            bottom_right.y - top_left.y + 1
+        );
+        let size = Size::new(
+            content.chars().count() as f32 * READOUT_TEXT_SIZE * 0.6 + 8.,
+            READOUT_TEXT_SIZE + 6.,
+        );
+        let x = (top_left.x as f32)
+            .min(self.size.width - size.width)
+            .max(0.);
+        let below = bottom_right.y as f32 + HANDLE_SIZE;
+        let y = if below + size.height < self.size.height {
+            below
+        } else {
+            (top_left.y as f32 - HANDLE_SIZE - size.height).max(0.)
+        };
+        fill(
+            renderer,
+            origin,
+            x,
+            y,
+            size.width,
+            size.height,
+            Color::from_rgba8(0, 0, 0, 0.7),
+        );
+        renderer.fill_text(
+            text::Text {
+                content: &content,
+                bounds: size,
+                size: Pixels(READOUT_TEXT_SIZE),
+                line_height: text::LineHeight::default(),
+                font: renderer.default_font(),
+                horizontal_alignment: alignment::Horizontal::Center,
+                vertical_alignment: alignment::Vertical::Center,
+                shaping: text::Shaping::Advanced,
+            },
+            Point::new(
+                origin.x + x + size.width / 2.,
+                origin.y + y + size.height / 2.,
+            ),
+            Color::WHITE,
+            Rectangle::new(Point::new(origin.x + x, origin.y + y), size),
+        );
+    }
+
+    /// Draws a magnified copy of the screenshot around `cursor`, beside it and kept on screen
+    fn draw_loupe<Renderer: renderer::Renderer + image::Renderer<Handle = Handle>>(
+        &self,
+        renderer: &mut Renderer,
+        bounds: Rectangle,
+        cursor: Point,
+    ) {
+        let mut x = cursor.x + LOUPE_OFFSET;
+        if x + LOUPE_SIZE > bounds.x + bounds.width {
+            x = cursor.x - LOUPE_OFFSET - LOUPE_SIZE;
+        }
+        let mut y = cursor.y + LOUPE_OFFSET;
+        if y + LOUPE_SIZE > bounds.y + bounds.height {
+            y = cursor.y - LOUPE_OFFSET - LOUPE_SIZE;
+        }
+        let loupe = Rectangle::new(Point::new(x, y), Size::new(LOUPE_SIZE, LOUPE_SIZE));
+
+        renderer.with_layer(loupe, |renderer| {
+            renderer.draw(
+                self.screenshot.clone(),
+                image::FilterMethod::Nearest,
+                Rectangle {
+                    x: loupe.center_x() - (cursor.x - bounds.x) * LOUPE_ZOOM,
+                    y: loupe.center_y() - (cursor.y - bounds.y) * LOUPE_ZOOM,
+                    width: self.size.width * LOUPE_ZOOM,
+                    height: self.size.height * LOUPE_ZOOM,
+                },
+            );
+        });
+        // Images are drawn above quads in the same layer, so the frame and crosshair need their own
+        renderer.with_layer(loupe, |renderer| {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: loupe,
+                    border: Border {
+                        color: self.style.colour,
+                        width: 2.,
+                        radius: 0.into(),
+                    },
+                    shadow: Shadow::default(),
+                },
+                Color::TRANSPARENT,
+            );
+            let origin = loupe.position();
+            let centre = LOUPE_SIZE / 2.;
+            fill(
+                renderer,
+                origin,
+                centre,
+                0.,
+                1.,
+                LOUPE_SIZE,
+                self.style.colour,
+            );
+            fill(
+                renderer,
+                origin,
+                0.,
+                centre,
+                LOUPE_SIZE,
+                1.,
+                self.style.colour,
+            );
+        });
+    }
+}
+
+impl Overlay {
+    /// Strokes the lines joining `points` as one shape, rather than a square for every pixel along them
+    fn draw_path(&self, renderer: &mut iced::Renderer, origin: Point, points: &[ImagePoint]) {
+        let mut frame = canvas::Frame::new(renderer, self.size);
+        frame.stroke(
+            &lasso_path(points),
+            canvas::Stroke::default()
+                .with_color(self.style.colour)
+                .with_width(self.style.thickness)
+                .with_line_join(canvas::LineJoin::Round),
+        );
+        renderer.with_translation(origin - Point::ORIGIN, |renderer| {
+            canvas::Renderer::draw(renderer, vec![frame.into_geometry()])
+        });
+    }
+}
+
+/// Straight lines joining each of `points` to the next, in image coordinates
+fn lasso_path(points: &[ImagePoint]) -> canvas::Path {
+    canvas::Path::new(|builder| {
+        let mut points = points.iter().map(|p| Point::new(p.x as f32, p.y as f32));
+        if let Some(first) = points.next() {
+            builder.move_to(first);
+            points.for_each(|p| builder.line_to(p));
+        }
+    })
+}
+
+/// Fills a rectangle given relative to `origin`
+fn fill<Renderer: renderer::Renderer>(
+    renderer: &mut Renderer,
+    origin: Point,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    colour: Color,
+) {
+    if width <= 0. || height <= 0. {
+        return;
+    }
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds: Rectangle {
+                x: origin.x + x,
+                y: origin.y + y,
+                width,
+                height,
+            },
+            border: Border::default(),
+            shadow: Shadow::default(),
+        },
+        colour,
+    );
+}
+
+// Lassos are drawn as canvas geometry, which needs the built in renderer
+impl<Message, Theme> Widget<Message, Theme, iced::Renderer> for Overlay {
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Fill, Length::Fill)
+    }
+
+    fn layout(
+        &self,
+        _tree: &mut Tree,
+        _renderer: &iced::Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        layout::Node::new(limits.max())
+    }
+
+    fn draw(
+        &self,
+        _tree: &Tree,
+        renderer: &mut iced::Renderer,
+        _theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let origin = bounds.position();
+        renderer.draw(
+            self.screenshot.clone(),
+            image::FilterMethod::Nearest,
+            Rectangle::new(origin, self.size),
+        );
+
+        // Anything that would fall off the screenshot is clipped by the layer
+        renderer.with_layer(Rectangle::new(origin, self.size), |renderer| {
+            for shape in &self.shapes {
+                match shape {
+                    Shape::Rectangle {
+                        top_left,
+                        bottom_right,
+                        handles,
+                    } => self.draw_outline(renderer, origin, *top_left, *bottom_right, *handles),
+                    Shape::Selection {
+                        top_left,
+                        bottom_right,
+                        handles,
+                    } => {
+                        self.draw_selection_area(renderer, origin, *top_left, *bottom_right);
+                        self.draw_outline(renderer, origin, *top_left, *bottom_right, *handles);
+                        if self.style.size_readout {
+                            self.draw_size_readout(renderer, origin, *top_left, *bottom_right);
+                        }
+                    }
+                    Shape::Path(points) => self.draw_path(renderer, origin, points),
+                }
+            }
+        });
+
+        if self.style.loupe
+            && let Some(position) = cursor.position_over(bounds)
+        {
+            self.draw_loupe(renderer, bounds, position);
+        }
+    }
+}
+
+impl<'a, Message, Theme> From<Overlay> for Element<'a, Message, Theme, iced::Renderer> {
+    fn from(overlay: Overlay) -> Self {
+        Element::new(overlay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// A lasso drawn around most of a 1920x1080 screenshot, one point per degree
+    fn screen_lasso() -> Vec<ImagePoint> {
+        (0..=360)
+            .map(|degrees| {
+                let angle = (degrees as f32).to_radians();
+                ImagePoint {
+                    x: (960. + 900. * angle.cos()) as u32,
+                    y: (540. + 500. * angle.sin()) as u32,
+                }
+            })
+            .collect()
+    }
+
+    /// How lassos used to be drawn, a square for every pixel along each line
+    fn squares_per_pixel(points: &[ImagePoint], thickness: f32) -> Vec<Rectangle> {
+        let mut squares = vec![];
+        for pair in points.windows(2) {
+            let (x0, y0) = (pair[0].x as f32, pair[0].y as f32);
+            let (x1, y1) = (pair[1].x as f32, pair[1].y as f32);
+            let steps = (x1 - x0).abs().max((y1 - y0).abs()).max(1.);
+            for step in 0..=steps as u32 {
+                let t = step as f32 / steps;
+                squares.push(Rectangle {
+                    x: x0 + (x1 - x0) * t - thickness / 2.,
+                    y: y0 + (y1 - y0) * t - thickness / 2.,
+                    width: thickness,
+                    height: thickness,
+                });
+            }
+        }
+        squares
+    }
+
+    #[test]
+    fn lasso_is_one_path_instead_of_a_square_per_pixel() {
+        let lasso = screen_lasso();
+        let squares = squares_per_pixel(&lasso, 2.);
+        let path = lasso_path(&lasso);
+
+        // Each square was its own quad for the renderer, thousands for a lasso this size
+        assert!(squares.len() > 4000);
+        // The path has a line for each point after the first, between its begin and end
+        assert_eq!(path.raw().iter().count(), lasso.len() + 1);
+    }
+
+    /// Size of the screenshot the selection is moved over, a 4K monitor
+    const BENCH_SIZE: (u32, u32) = (3840, 2160);
+
+    /// What every mouse move used to cost: a fresh copy of the screenshot with the selection drawn into it,
+    /// hashed into a new handle that the renderer then had to upload again
+    fn redraw_into_copy(
+        screenshot: &[u8],
+        top_left: ImagePoint,
+        bottom_right: ImagePoint,
+    ) -> Handle {
+        let mut image = vec![0; screenshot.len()];
+        image.copy_from_slice(screenshot);
+        let colour = [255, 0, 0, 255];
+        let mut set = |x: u32, y: u32| {
+            let index = ((y * BENCH_SIZE.0 + x) * 4) as usize;
+            image[index..index + 4].copy_from_slice(&colour);
+        };
+        for x in top_left.x..=bottom_right.x {
+            set(x, top_left.y);
+            set(x, bottom_right.y);
+        }
+        for y in top_left.y..=bottom_right.y {
+            set(top_left.x, y);
+            set(bottom_right.x, y);
+        }
+        Handle::from_pixels(BENCH_SIZE.0, BENCH_SIZE.1, image)
+    }
+
+    #[test]
+    #[ignore = "timing benchmark, run with `cargo test --release -- --ignored`"]
+    fn moving_the_selection_does_not_copy_the_screenshot() {
+        let screenshot = vec![128; (BENCH_SIZE.0 * BENCH_SIZE.1 * 4) as usize];
+        let cached = Handle::from_pixels(BENCH_SIZE.0, BENCH_SIZE.1, screenshot.clone());
+        let settings = Settings::initial();
+        let moves = 20;
+        let corners = |i: u32| {
+            (
+                ImagePoint {
+                    x: 100 + i,
+                    y: 100 + i,
+                },
+                ImagePoint {
+                    x: 1900 + i,
+                    y: 1000 + i,
+                },
+            )
+        };
+
+        let start = Instant::now();
+        for i in 0..moves {
+            let (top_left, bottom_right) = corners(i);
+            std::hint::black_box(redraw_into_copy(&screenshot, top_left, bottom_right));
+        }
+        let copy_time = start.elapsed();
+
+        // The layer keeps the cached handle and only has the shapes to turn into a few quads when drawn
+        let start = Instant::now();
+        for i in 0..moves {
+            let (top_left, bottom_right) = corners(i);
+            std::hint::black_box(Overlay::new(
+                cached.clone(),
+                BENCH_SIZE,
+                &settings,
+                vec![Shape::Selection {
+                    top_left,
+                    bottom_right,
+                    handles: true,
+                }],
+            ));
+        }
+        let layer_time = start.elapsed();
+
+        assert!(
+            layer_time * 100 < copy_time,
+            "{moves} moves took {copy_time:?} copying the screenshot and {layer_time:?} with the layer"
+        );
+    }
+
+    #[test]
+    fn empty_lasso_is_an_empty_path() {
+        assert_eq!(lasso_path(&[]).raw().iter().count(), 0);
+    }
+}
@@ -0,0 +1,250 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+use crate::options::Settings;
+use serde_json::Value;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Prefix for environment variables that override settings, e.g. `DRAW_READ_RATE=200`
+pub const ENV_PREFIX: &str = "DRAW_READ_";
+
+static OVERRIDES: OnceLock<Overrides> = OnceLock::new();
+
+/// Settings given on the command line or in the environment, layered over `settings.json`
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// Settings file to use instead of the one in the user's config dir
+    pub config_file: Option<PathBuf>,
+    pub profile: Option<String>,
+    /// Setting field names and their unparsed values, applied in order
+    values: Vec<(String, String)>,
+}
+
+impl Overrides {
+    /// Reads `DRAW_READ_*` variables from `vars`, then `--setting value` pairs from `args`,
+    /// so the command line wins over the environment. Other variables aren't looked at, so they
+    /// don't have to be valid text
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        vars: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Result<Self, String> {
+        let mut overrides = Self::default();
+
+        for (key, value) in vars {
+            let key = key.to_string_lossy();
+            let Some(name) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let value = value
+                .into_string()
+                .map_err(|value| format!("Invalid value for {key}: {value:?} isn't valid text"))?;
+            overrides.set(&name.to_lowercase(), value);
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(arg) = arg.strip_prefix("--") else {
+                return Err(format!("Unexpected argument {arg:?}"));
+            };
+            let (key, value) = match arg.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (
+                    arg.to_string(),
+                    args.next()
+                        .ok_or_else(|| format!("Missing value for --{arg}"))?,
+                ),
+            };
+            overrides.set(&key, value);
+        }
+
+        // Check names and values up front rather than failing later when settings are reloaded
+        overrides.apply_values(Settings::initial())?;
+        Ok(overrides)
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        match normalise_key(key).as_str() {
+            "config" => self.config_file = Some(value.into()),
+            "profile" => self.profile = Some(value),
+            key => self.values.push((key.to_string(), value)),
+        }
+    }
+
+    /// Returns `settings` with the overrides applied on top
+    pub fn apply(&self, settings: &Settings) -> Result<Settings, String> {
+        let mut settings = settings.clone();
+        if let Some(profile) = &self.profile {
+            let index = settings
+                .profiles
+                .iter()
+                .position(|p| &p.name == profile)
+                .ok_or_else(|| format!("No profile named {profile:?}"))?;
+            settings.switch_profile(index);
+        }
+        self.apply_values(settings)
+    }
+
+    fn apply_values(&self, settings: Settings) -> Result<Settings, String> {
+        let mut settings_value = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+        for (key, value) in &self.values {
+            let field = settings_value
+                .get_mut(key)
+                .ok_or_else(|| format!("Unknown setting --{}", key.replace('_', "-")))?;
+            // Text settings take the value as-is, anything else is read as JSON so numbers,
+            // booleans and lists like `[255,0,0,255]` all work
+            *field = if field.is_string() {
+                Value::String(value.clone())
+            } else {
+                serde_json::from_str(value).map_err(|e| format!("Invalid value for {key}: {e}"))?
+            };
+        }
+        serde_json::from_value(settings_value).map_err(|e| format!("Invalid setting: {e}"))
+    }
+}
+
+/// Accepts `--detection-model`, `DRAW_READ_DETECTION_MODEL` etc. as well as the field names in `settings.json`
+fn normalise_key(key: &str) -> String {
+    match key.replace('-', "_").as_str() {
+        "detection_model" => "detection_file".to_string(),
+        "recognition_model" => "recognition_file".to_string(),
+        "rect_color" => "rect_colour".to_string(),
+        key => key.to_string(),
+    }
+}
+
+pub fn usage() -> String {
+    let settings_names = serde_json::to_value(Settings::initial())
+        .ok()
+        .and_then(|v| v.as_object().cloned())
+        .map(|fields| {
+            fields
+                .keys()
+                .filter(|k| !matches!(k.as_str(), "version" | "profiles" | "active_profile"))
+                .map(|k| format!("  --{}", k.replace('_', "-")))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default();
+    format!(
+        "Usage: draw-read [--config <path>] [--profile <name>] [--<setting> <value>]...\n\n\
+         Any setting can also be given as an environment variable, e.g. {ENV_PREFIX}RATE=200.\n\
+         Values other than text are JSON, e.g. --rect-colour [255,0,0,255]\n\n\
+         Settings:\n{settings_names}\n  --detection-model\n  --recognition-model"
+    )
+}
+
+/// Makes the overrides visible to `Settings::load`, must only be called once at startup
+pub fn init(overrides: Overrides) {
+    if OVERRIDES.set(overrides).is_err() {
+        eprintln!("Overrides were already set");
+    }
+}
+
+pub fn get() -> Option<&'static Overrides> {
+    OVERRIDES.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        vars.iter().map(|(k, v)| (k.into(), v.into())).collect()
+    }
+
+    /// A variable value that can't be turned into a `String`
+    fn not_text() -> OsString {
+        #[cfg(unix)]
+        return std::os::unix::ffi::OsStringExt::from_vec(vec![b'a', 0xff]);
+        #[cfg(windows)]
+        return std::os::windows::ffi::OsStringExt::from_wide(&[b'a' as u16, 0xD800]);
+    }
+
+    #[test]
+    fn accepts_both_argument_forms() {
+        let overrides = Overrides::parse(args(&["--rate", "200", "--volume=100"]), vec![]).unwrap();
+        let settings = overrides.apply(&Settings::initial()).unwrap();
+        assert_eq!(settings.rate, 200);
+        assert_eq!(settings.volume, 100);
+    }
+
+    #[test]
+    fn command_line_wins_over_environment() {
+        let overrides = Overrides::parse(
+            args(&["--rate", "200"]),
+            vars(&[
+                ("DRAW_READ_RATE", "300"),
+                ("DRAW_READ_VOLUME", "100"),
+                ("HOME", "/"),
+            ]),
+        )
+        .unwrap();
+        let settings = overrides.apply(&Settings::initial()).unwrap();
+        assert_eq!(settings.rate, 200);
+        assert_eq!(settings.volume, 100);
+    }
+
+    #[test]
+    fn text_settings_are_taken_as_is() {
+        let overrides = Overrides::parse(args(&["--voice", "Zira"]), vec![]).unwrap();
+        assert_eq!(overrides.apply(&Settings::initial()).unwrap().voice, "Zira");
+    }
+
+    #[test]
+    fn reads_config_and_profile() {
+        let overrides = Overrides::parse(
+            args(&["--config", "/tmp/settings.json"]),
+            vars(&[("DRAW_READ_PROFILE", "Reading")]),
+        )
+        .unwrap();
+        assert_eq!(
+            overrides.config_file,
+            Some(PathBuf::from("/tmp/settings.json"))
+        );
+        assert_eq!(overrides.profile.as_deref(), Some("Reading"));
+        // Unknown profiles are only found out once there are settings to look in
+        assert!(overrides.apply(&Settings::initial()).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        for bad in [
+            &["rate", "200"][..],
+            &["--rate"],
+            &["--loudness", "3"],
+            &["--rate", "fast"],
+        ] {
+            assert!(Overrides::parse(args(bad), vec![]).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn ignores_other_variables_that_are_not_text() {
+        let mut environment = vars(&[("DRAW_READ_VOLUME", "100")]);
+        environment.push(("UNRELATED".into(), not_text()));
+        let overrides = Overrides::parse(vec![], environment).unwrap();
+        assert_eq!(overrides.apply(&Settings::initial()).unwrap().volume, 100);
+    }
+
+    #[test]
+    fn rejects_override_variables_that_are_not_text() {
+        let environment = vec![("DRAW_READ_VOICE".into(), not_text())];
+        let error = Overrides::parse(vec![], environment).unwrap_err();
+        assert!(error.contains("DRAW_READ_VOICE"), "{error}");
+    }
+
+    #[test]
+    fn normalises_keys() {
+        assert_eq!(normalise_key("reading-mode"), "reading_mode");
+        assert_eq!(normalise_key("detection-model"), "detection_file");
+        assert_eq!(normalise_key("recognition_model"), "recognition_file");
+        assert_eq!(normalise_key("rect-color"), "rect_colour");
+        assert_eq!(normalise_key("rect_colour"), "rect_colour");
+    }
+}
@@ -0,0 +1,427 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+use crate::iced_logic::ScreenPoint;
+
+/// Smallest selection, in screen pixels along each side, that will be read
+pub const MIN_SELECTION_SIZE: i32 = 8;
+/// How close, in screen pixels, the mouse has to be to an edge or corner to grab it
+pub const HANDLE_GRAB_DISTANCE: i32 = 6;
+/// Minimum distance between recorded points of a freehand lasso, to keep the polygon small
+pub const LASSO_POINT_SPACING: i32 = 4;
+
+/// Part of a selection that can be grabbed with the mouse to change it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Handle {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+    /// Anywhere inside the selection, moves it without resizing
+    Inside,
+}
+
+/// Where the user is in selecting an area of the screenshot overlay
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Selection {
+    /// No screenshot is showing
+    #[default]
+    Closed,
+    /// The screenshot is showing but nothing is selected
+    Empty,
+    /// The mouse is held down and the selection follows it
+    Dragging {
+        start: ScreenPoint,
+        end: ScreenPoint,
+    },
+    /// A finished selection that can still be adjusted, waiting for Enter
+    Selected {
+        start: ScreenPoint,
+        end: ScreenPoint,
+    },
+    /// A handle of a finished selection is being dragged, `from` is where the mouse last was
+    Adjusting {
+        start: ScreenPoint,
+        end: ScreenPoint,
+        handle: Handle,
+        from: ScreenPoint,
+    },
+}
+
+impl Selection {
+    pub fn is_open(&self) -> bool {
+        *self != Selection::Closed
+    }
+
+    pub fn corners(&self) -> Option<(ScreenPoint, ScreenPoint)> {
+        match *self {
+            Selection::Dragging { start, end }
+            | Selection::Selected { start, end }
+            | Selection::Adjusting { start, end, .. } => Some((start, end)),
+            Selection::Closed | Selection::Empty => None,
+        }
+    }
+
+    /// Whether the selection is following the mouse
+    pub fn is_dragging(&self) -> bool {
+        matches!(
+            self,
+            Selection::Dragging { .. } | Selection::Adjusting { .. }
+        )
+    }
+
+    /// Grabs a handle of the current selection if `pos` is on one, otherwise starts a new selection
+    pub fn press(&mut self, pos: ScreenPoint) {
+        if let Selection::Selected { start, end } = *self
+            && let Some(handle) = handle_at(start, end, pos)
+        {
+            let (start, end) = normalise(start, end);
+            *self = Selection::Adjusting {
+                start,
+                end,
+                handle,
+                from: pos,
+            };
+        } else if self.is_open() {
+            *self = Selection::Dragging {
+                start: pos,
+                end: pos,
+            };
+        }
+    }
+
+    pub fn drag_to(&mut self, pos: ScreenPoint) {
+        match self {
+            Selection::Dragging { end, .. } => *end = pos,
+            Selection::Adjusting {
+                start,
+                end,
+                handle,
+                from,
+            } => {
+                let (dx, dy) = (pos.x - from.x, pos.y - from.y);
+                *from = pos;
+                let (move_left, move_top, move_right, move_bottom) = match handle {
+                    Handle::TopLeft => (true, true, false, false),
+                    Handle::Top => (false, true, false, false),
+                    Handle::TopRight => (false, true, true, false),
+                    Handle::Right => (false, false, true, false),
+                    Handle::BottomRight => (false, false, true, true),
+                    Handle::Bottom => (false, false, false, true),
+                    Handle::BottomLeft => (true, false, false, true),
+                    Handle::Left => (true, false, false, false),
+                    Handle::Inside => (true, true, true, true),
+                };
+                if move_left {
+                    start.x += dx;
+                }
+                if move_top {
+                    start.y += dy;
+                }
+                if move_right {
+                    end.x += dx;
+                }
+                if move_bottom {
+                    end.y += dy;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Keeps both corners between `min` and `max`, without changing what is being done to the selection.
+    /// A selection being moved as a whole stops at the edge instead of shrinking against it
+    pub fn clamp(&mut self, min: ScreenPoint, max: ScreenPoint) {
+        let clamp_point = |p: ScreenPoint| ScreenPoint {
+            x: p.x.clamp(min.x, max.x),
+            y: p.y.clamp(min.y, max.y),
+        };
+        match self {
+            Selection::Adjusting {
+                start,
+                end,
+                handle: Handle::Inside,
+                ..
+            } => {
+                let (top_left, bottom_right) = normalise(*start, *end);
+                let shift = |low: i32, high: i32, min: i32, max: i32| {
+                    if low < min {
+                        min - low
+                    } else if high > max {
+                        max - high
+                    } else {
+                        0
+                    }
+                };
+                let dx = shift(top_left.x, bottom_right.x, min.x, max.x);
+                let dy = shift(top_left.y, bottom_right.y, min.y, max.y);
+                for corner in [start, end] {
+                    *corner = clamp_point(ScreenPoint {
+                        x: corner.x + dx,
+                        y: corner.y + dy,
+                    });
+                }
+            }
+            Selection::Dragging { start, end }
+            | Selection::Selected { start, end }
+            | Selection::Adjusting { start, end, .. } => {
+                *start = clamp_point(*start);
+                *end = clamp_point(*end);
+            }
+            Selection::Closed | Selection::Empty => {}
+        }
+    }
+
+    /// Finishes a drag, returning the corners of a newly drawn selection if it is big enough to read.
+    /// A click or a tiny drag goes back to an empty selection so it can't be read later by mistake.
+    /// Finishing an adjustment leaves the selection waiting for Enter
+    pub fn release(&mut self) -> Option<(ScreenPoint, ScreenPoint)> {
+        match *self {
+            Selection::Dragging { start, end } => {
+                if is_large_enough(start, end) {
+                    *self = Selection::Selected { start, end };
+                    return Some((start, end));
+                }
+                println!("Selection too small to read");
+                *self = Selection::Empty;
+            }
+            Selection::Adjusting { start, end, .. } => {
+                let (start, end) = normalise(start, end);
+                *self = Selection::Selected { start, end };
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Replaces the selection, e.g. with one made with the keyboard
+    pub fn select(&mut self, start: ScreenPoint, end: ScreenPoint) {
+        if self.is_open() {
+            *self = Selection::Selected { start, end };
+        }
+    }
+
+    /// Returns the corners of a keyboard selection if it is big enough to read
+    pub fn confirm(&self) -> Option<(ScreenPoint, ScreenPoint)> {
+        match *self {
+            Selection::Selected { start, end } if is_large_enough(start, end) => Some((start, end)),
+            _ => None,
+        }
+    }
+}
+
+pub fn is_large_enough(start: ScreenPoint, end: ScreenPoint) -> bool {
+    (start.x - end.x).abs() >= MIN_SELECTION_SIZE && (start.y - end.y).abs() >= MIN_SELECTION_SIZE
+}
+
+/// Which handle of the selection from `start` to `end` is under `pos`, if any
+pub fn handle_at(start: ScreenPoint, end: ScreenPoint, pos: ScreenPoint) -> Option<Handle> {
+    let (top_left, bottom_right) = normalise(start, end);
+    let near = |a: i32, b: i32| (a - b).abs() <= HANDLE_GRAB_DISTANCE;
+    if pos.x < top_left.x - HANDLE_GRAB_DISTANCE
+        || pos.x > bottom_right.x + HANDLE_GRAB_DISTANCE
+        || pos.y < top_left.y - HANDLE_GRAB_DISTANCE
+        || pos.y > bottom_right.y + HANDLE_GRAB_DISTANCE
+    {
+        return None;
+    }
+    let handle = match (
+        near(pos.x, top_left.x),
+        near(pos.y, top_left.y),
+        near(pos.x, bottom_right.x),
+        near(pos.y, bottom_right.y),
+    ) {
+        (true, true, _, _) => Handle::TopLeft,
+        (_, true, true, _) => Handle::TopRight,
+        (_, _, true, true) => Handle::BottomRight,
+        (true, _, _, true) => Handle::BottomLeft,
+        (true, _, _, _) => Handle::Left,
+        (_, true, _, _) => Handle::Top,
+        (_, _, true, _) => Handle::Right,
+        (_, _, _, true) => Handle::Bottom,
+        _ => Handle::Inside,
+    };
+    Some(handle)
+}
+
+/// The top left and bottom right corners of the rectangle between `start` and `end`
+pub fn normalise(start: ScreenPoint, end: ScreenPoint) -> (ScreenPoint, ScreenPoint) {
+    (
+        ScreenPoint {
+            x: start.x.min(end.x),
+            y: start.y.min(end.y),
+        },
+        ScreenPoint {
+            x: start.x.max(end.x),
+            y: start.y.max(end.y),
+        },
+    )
+}
+
+/// The top left and bottom right corners of the smallest rectangle around `points`
+pub fn bounding_box(points: &[ScreenPoint]) -> Option<(ScreenPoint, ScreenPoint)> {
+    let first = *points.first()?;
+    Some(
+        points
+            .iter()
+            .fold((first, first), |(top_left, bottom_right), p| {
+                normalise(normalise(top_left, *p).0, normalise(bottom_right, *p).1)
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: i32, y: i32) -> ScreenPoint {
+        ScreenPoint { x, y }
+    }
+
+    #[test]
+    fn moving_stops_at_the_edge_without_shrinking() {
+        let mut selection = Selection::Selected {
+            start: point(10, 10),
+            end: point(50, 30),
+        };
+        selection.press(point(30, 20));
+        selection.drag_to(point(10, 0));
+        selection.clamp(point(0, 0), point(99, 99));
+        assert_eq!(selection.corners(), Some((point(0, 0), point(40, 20))));
+
+        // Resizing against the edge still stops that side only
+        let mut selection = Selection::Dragging {
+            start: point(10, 10),
+            end: point(150, 30),
+        };
+        selection.clamp(point(0, 0), point(99, 99));
+        assert_eq!(selection.corners(), Some((point(10, 10), point(99, 30))));
+    }
+
+    #[test]
+    fn press_drag_release_selects() {
+        let mut selection = Selection::Empty;
+        selection.press(point(10, 10));
+        assert!(selection.is_dragging());
+        selection.drag_to(point(60, 40));
+        assert_eq!(selection.corners(), Some((point(10, 10), point(60, 40))));
+
+        assert_eq!(selection.release(), Some((point(10, 10), point(60, 40))));
+        assert_eq!(
+            selection,
+            Selection::Selected {
+                start: point(10, 10),
+                end: point(60, 40)
+            }
+        );
+        assert!(!selection.is_dragging());
+    }
+
+    #[test]
+    fn click_or_tiny_drag_selects_nothing() {
+        let mut selection = Selection::Empty;
+        selection.press(point(10, 10));
+        selection.drag_to(point(10 + MIN_SELECTION_SIZE - 1, 60));
+        assert_eq!(selection.release(), None);
+        assert_eq!(selection, Selection::Empty);
+        assert_eq!(selection.confirm(), None);
+    }
+
+    #[test]
+    fn closed_selection_ignores_the_mouse() {
+        let mut selection = Selection::Closed;
+        selection.press(point(10, 10));
+        selection.drag_to(point(60, 40));
+        assert_eq!(selection.release(), None);
+        assert_eq!(selection, Selection::Closed);
+    }
+
+    #[test]
+    fn min_size_threshold() {
+        let size = MIN_SELECTION_SIZE;
+        assert!(is_large_enough(point(0, 0), point(size, size)));
+        assert!(is_large_enough(point(size, size), point(0, 0)));
+        assert!(!is_large_enough(point(0, 0), point(size - 1, size)));
+        assert!(!is_large_enough(point(0, 0), point(size, size - 1)));
+    }
+
+    #[test]
+    fn finds_handles_at_corners_and_edges() {
+        // Given backwards, as when a selection is drawn up and to the left
+        let (start, end) = (point(100, 80), point(10, 20));
+        let grab = HANDLE_GRAB_DISTANCE;
+        for (pos, handle) in [
+            (point(10, 20), Handle::TopLeft),
+            (point(10 - grab, 20 - grab), Handle::TopLeft),
+            (point(55, 20), Handle::Top),
+            (point(100 + grab, 20), Handle::TopRight),
+            (point(100, 50), Handle::Right),
+            (point(100, 80), Handle::BottomRight),
+            (point(55, 80 + grab), Handle::Bottom),
+            (point(10, 80), Handle::BottomLeft),
+            (point(10, 50), Handle::Left),
+            (point(55, 50), Handle::Inside),
+        ] {
+            assert_eq!(handle_at(start, end, pos), Some(handle), "{pos}");
+        }
+        for pos in [point(10 - grab - 1, 50), point(55, 80 + grab + 1)] {
+            assert_eq!(handle_at(start, end, pos), None, "{pos}");
+        }
+    }
+
+    #[test]
+    fn dragging_a_corner_only_moves_that_corner() {
+        let mut selection = Selection::Selected {
+            start: point(10, 20),
+            end: point(100, 80),
+        };
+        selection.press(point(100, 80));
+        assert!(matches!(
+            selection,
+            Selection::Adjusting {
+                handle: Handle::BottomRight,
+                ..
+            }
+        ));
+        selection.drag_to(point(120, 90));
+        assert_eq!(selection.corners(), Some((point(10, 20), point(120, 90))));
+
+        // Dragged past the opposite corner, it's turned the right way round when let go
+        selection.drag_to(point(0, 0));
+        selection.release();
+        assert_eq!(selection.corners(), Some((point(0, 0), point(10, 20))));
+    }
+
+    #[test]
+    fn pressing_outside_a_selection_starts_a_new_one() {
+        let mut selection = Selection::Selected {
+            start: point(10, 20),
+            end: point(100, 80),
+        };
+        selection.press(point(300, 300));
+        assert_eq!(
+            selection,
+            Selection::Dragging {
+                start: point(300, 300),
+                end: point(300, 300)
+            }
+        );
+    }
+
+    #[test]
+    fn normalises_corners() {
+        assert_eq!(
+            normalise(point(50, 10), point(10, 40)),
+            (point(10, 10), point(50, 40))
+        );
+        assert_eq!(
+            bounding_box(&[point(5, 9), point(1, 20), point(8, 2)]),
+            Some((point(1, 2), point(8, 20)))
+        );
+        assert_eq!(bounding_box(&[]), None);
+    }
+}
@@ -0,0 +1,321 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+use crate::options::Settings;
+use crate::overrides::Overrides;
+use serde_json::Value;
+use std::ops::Deref;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Changes in the settings panel this close together are undone as one, like a slider being dragged
+pub const UNDO_MERGE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The settings in use, alongside changes made in the settings panel that only take effect once applied.
+/// Dereferences to the pending settings, which is what the panel shows
+#[derive(Debug, Clone)]
+pub struct SettingsState {
+    /// What `settings.json` holds, without overrides. This is all that gets saved
+    file: Settings,
+    /// Settings given on the command line or in the environment, layered over `file` for this run only
+    overrides: Overrides,
+    /// The settings last applied or saved, which the OCR engine, voice and window are set up with
+    committed: Settings,
+    /// `committed` plus any changes made in the settings panel since
+    pending: Settings,
+    /// Pending settings as they were before each change in the panel, for undo
+    history: Vec<Settings>,
+    last_change: Option<Instant>,
+}
+
+impl SettingsState {
+    pub fn with_overrides(file: Settings, overrides: Overrides) -> Self {
+        let settings = layer(&file, &overrides);
+        Self {
+            file,
+            overrides,
+            committed: settings.clone(),
+            pending: settings,
+            history: vec![],
+            last_change: None,
+        }
+    }
+
+    pub fn committed(&self) -> &Settings {
+        &self.committed
+    }
+
+    /// The settings to save, which only has overridden settings that were changed in the app
+    pub fn file(&self) -> &Settings {
+        &self.file
+    }
+
+    /// Whether the settings panel has changes that haven't been applied
+    pub fn is_dirty(&self) -> bool {
+        self.pending != self.committed
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    /// Makes a change in the settings panel at `now`, which can be undone and isn't used until it's applied
+    pub fn change(&mut self, f: impl FnOnce(&mut Settings), now: Instant) {
+        // A slider being dragged makes many changes, undo them together
+        let merge = !self.history.is_empty()
+            && self
+                .last_change
+                .is_some_and(|last| now.saturating_duration_since(last) <= UNDO_MERGE_INTERVAL);
+        if !merge {
+            self.history.push(self.pending.clone());
+        }
+        self.last_change = Some(now);
+        f(&mut self.pending);
+    }
+
+    /// Goes back to the pending settings as they were before the last change
+    pub fn undo(&mut self) {
+        if let Some(previous) = self.history.pop() {
+            self.pending = previous;
+        }
+        self.last_change = None;
+    }
+
+    /// Drops every pending change
+    pub fn cancel(&mut self) {
+        self.pending = self.committed.clone();
+        self.history.clear();
+        self.last_change = None;
+    }
+
+    /// Commits the pending changes, returning the settings they replace.
+    /// Only settings changed in the panel are copied to the file, overrides that weren't touched stay out of it
+    pub fn apply(&mut self) -> Settings {
+        self.file = merge_changes(&self.file, &self.committed, &self.pending);
+        self.history.clear();
+        self.last_change = None;
+        std::mem::replace(&mut self.committed, self.pending.clone())
+    }
+
+    /// Makes a change outside the settings panel, such as moving the window, that takes effect straight away
+    /// and is kept whether changes in the panel are applied, cancelled or undone
+    pub fn commit(&mut self, f: impl Fn(&mut Settings)) {
+        f(&mut self.file);
+        f(&mut self.pending);
+        f(&mut self.committed);
+        self.history.iter_mut().for_each(&f);
+    }
+
+    /// Replaces the file settings, e.g. when the settings file changed, dropping any pending changes.
+    /// The window position is kept, the window is wherever it was moved to rather than where the file says.
+    /// Returns the settings they replace
+    pub fn replace(&mut self, mut file: Settings) -> Settings {
+        self.cancel();
+        file.position = self.file.position;
+        let settings = layer(&file, &self.overrides);
+        self.file = file;
+        self.pending = settings.clone();
+        std::mem::replace(&mut self.committed, settings)
+    }
+}
+
+/// `file` with `overrides` on top, kept in range as overrides aren't checked against it
+fn layer(file: &Settings, overrides: &Overrides) -> Settings {
+    let mut settings = overrides.apply(file).unwrap_or_else(|e| {
+        eprintln!("Error applying overrides: {e}");
+        file.clone()
+    });
+    settings.clamp_values();
+    settings
+}
+
+/// `file` with each top level setting that differs between `before` and `after` set to its value in `after`
+fn merge_changes(file: &Settings, before: &Settings, after: &Settings) -> Settings {
+    let (Ok(mut file_value), Ok(before), Ok(Value::Object(after))) = (
+        serde_json::to_value(file),
+        serde_json::to_value(before),
+        serde_json::to_value(after),
+    ) else {
+        return after.clone();
+    };
+    for (key, value) in after {
+        if before.get(&key) != Some(&value) {
+            file_value[key] = value;
+        }
+    }
+    serde_json::from_value(file_value).unwrap_or_else(|e| {
+        eprintln!("Error merging settings: {e}");
+        file.clone()
+    })
+}
+
+impl Deref for SettingsState {
+    type Target = Settings;
+
+    fn deref(&self) -> &Settings {
+        &self.pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iced_logic::ScreenPoint;
+
+    fn state() -> SettingsState {
+        SettingsState::with_overrides(Settings::initial(), Overrides::default())
+    }
+
+    #[test]
+    fn cancel_drops_pending_changes() {
+        let mut state = state();
+        state.change(|s| s.volume = 10, Instant::now());
+        assert!(state.is_dirty());
+        assert_eq!(state.committed().volume, 255);
+
+        state.cancel();
+        assert_eq!(state.volume, 255);
+        assert!(!state.is_dirty());
+        assert!(!state.can_undo());
+    }
+
+    #[test]
+    fn apply_commits_pending_changes() {
+        let mut state = state();
+        state.change(|s| s.volume = 10, Instant::now());
+
+        let previous = state.apply();
+        assert_eq!(previous.volume, 255);
+        assert_eq!(state.committed().volume, 10);
+        assert!(!state.is_dirty());
+        assert!(!state.can_undo());
+    }
+
+    #[test]
+    fn undo_steps_back_one_change() {
+        let mut state = state();
+        let start = Instant::now();
+        state.change(|s| s.volume = 10, start);
+        state.change(|s| s.rate = 200, start + UNDO_MERGE_INTERVAL * 2);
+
+        state.undo();
+        assert_eq!(state.rate, Settings::initial().rate);
+        assert_eq!(state.volume, 10);
+        assert!(state.is_dirty());
+
+        state.undo();
+        assert_eq!(state.volume, 255);
+        assert!(!state.is_dirty());
+        assert!(!state.can_undo());
+    }
+
+    #[test]
+    fn quick_changes_undo_together() {
+        let mut state = state();
+        let start = Instant::now();
+        for (i, volume) in [10, 20, 30].into_iter().enumerate() {
+            state.change(
+                |s| s.volume = volume,
+                start + UNDO_MERGE_INTERVAL / 4 * i as u32,
+            );
+        }
+        state.change(|s| s.rate = 200, start + UNDO_MERGE_INTERVAL * 4);
+
+        state.undo();
+        assert_eq!(state.volume, 30);
+        state.undo();
+        assert_eq!(state.volume, 255);
+        assert!(!state.can_undo());
+    }
+
+    #[test]
+    fn committed_changes_survive_cancel_and_undo() {
+        let mut state = state();
+        let moved = ScreenPoint { x: 40, y: 50 };
+        state.change(|s| s.volume = 10, Instant::now());
+        state.commit(|s| s.position = moved);
+        assert_eq!(state.committed().position, moved);
+
+        state.undo();
+        assert_eq!(state.position, moved);
+        state.change(|s| s.volume = 10, Instant::now());
+        state.cancel();
+        assert_eq!(state.position, moved);
+        assert_eq!(state.volume, 255);
+    }
+
+    #[test]
+    fn replace_drops_pending_changes() {
+        let mut state = state();
+        state.change(|s| s.volume = 10, Instant::now());
+        let mut from_file = Settings::initial();
+        from_file.rate = 150;
+
+        let previous = state.replace(from_file);
+        assert_eq!(previous.rate, Settings::initial().rate);
+        assert_eq!(state.rate, 150);
+        assert_eq!(state.volume, 255);
+        assert!(!state.is_dirty());
+    }
+
+    #[test]
+    fn replace_keeps_the_window_position() {
+        let mut state = state();
+        let moved = ScreenPoint { x: 40, y: 50 };
+        state.commit(|s| s.position = moved);
+        let mut from_file = Settings::initial();
+        from_file.position = ScreenPoint { x: 0, y: 0 };
+
+        state.replace(from_file);
+        assert_eq!(state.position, moved);
+        assert_eq!(state.file().position, moved);
+    }
+
+    fn overridden_state(args: &[&str]) -> SettingsState {
+        let overrides =
+            Overrides::parse(args.iter().map(|a| a.to_string()), std::iter::empty()).unwrap();
+        SettingsState::with_overrides(Settings::initial(), overrides)
+    }
+
+    #[test]
+    fn overrides_are_not_saved() {
+        let mut state = overridden_state(&["--rate", "150"]);
+        assert_eq!(state.committed().rate, 150);
+        assert_eq!(state.file().rate, Settings::initial().rate);
+
+        state.change(|s| s.volume = 10, Instant::now());
+        state.apply();
+        state.commit(|s| s.magnifier_zoom = 3.);
+        assert_eq!(state.file().volume, 10);
+        assert_eq!(state.file().magnifier_zoom, 3.);
+        assert_eq!(state.file().rate, Settings::initial().rate);
+        assert_eq!(state.committed().rate, 150);
+    }
+
+    #[test]
+    fn overridden_setting_changed_in_the_panel_is_saved() {
+        let mut state = overridden_state(&["--rate", "150"]);
+        state.change(|s| s.rate = 220, Instant::now());
+        state.apply();
+        assert_eq!(state.file().rate, 220);
+        assert_eq!(state.committed().rate, 220);
+    }
+
+    #[test]
+    fn overrides_are_clamped() {
+        let state = overridden_state(&["--rate", "5000", "--pitch", "-2"]);
+        assert_eq!(state.rate, crate::options::MAX_RATE);
+        assert_eq!(state.pitch, 0.);
+    }
+
+    #[test]
+    fn overrides_stay_on_replaced_file() {
+        let mut state = overridden_state(&["--rate", "150"]);
+        let mut from_file = Settings::initial();
+        from_file.volume = 10;
+        state.replace(from_file);
+        assert_eq!(state.rate, 150);
+        assert_eq!(state.volume, 10);
+        assert_eq!(state.file().rate, Settings::initial().rate);
+    }
+}
@@ -0,0 +1,346 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+/// Recognised text split into lines and sentences, with a cursor tracking what is being spoken
+#[derive(Debug, Clone, Default)]
+pub struct SpeechQueue {
+    /// Each line's sentences as they are spoken, after pronunciations or spelling out
+    lines: Vec<Vec<String>>,
+    /// The same sentences as they were recognised, to show on screen
+    text: Vec<Vec<String>>,
+    /// The language each line is written in, when known
+    languages: Vec<Option<String>>,
+    pub line: usize,
+    pub sentence: usize,
+    pub paused: bool,
+}
+
+impl SpeechQueue {
+    /// Recognised lines with the language of each, so they can be read with a matching voice.
+    /// `speak` turns each sentence into what is sent to the voice, while the text shown stays as recognised
+    pub fn with_languages(
+        lines: Vec<(String, Option<String>)>,
+        speak: impl Fn(&str) -> String,
+    ) -> Self {
+        let mut queue = Self::default();
+        for (line, language) in lines {
+            let sentences = split_sentences(&line);
+            if sentences.is_empty() {
+                continue;
+            }
+            queue
+                .lines
+                .push(sentences.iter().map(|s| speak(s)).collect());
+            queue.text.push(sentences);
+            queue.languages.push(language);
+        }
+        queue
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Whether both queues hold the same text, wherever they are up to
+    pub fn same_text(&self, other: &SpeechQueue) -> bool {
+        self.text == other.text
+    }
+
+    /// Every sentence as recognised, grouped by line
+    pub fn text(&self) -> &[Vec<String>] {
+        &self.text
+    }
+
+    /// The language of the line being read, if known
+    pub fn language(&self) -> Option<&str> {
+        self.languages.get(self.line).and_then(|l| l.as_deref())
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.lines
+            .get(self.line)
+            .and_then(|l| l.get(self.sentence))
+            .map(|s| s.as_str())
+    }
+
+    /// Moves to the next sentence, returning false once the end of the text is reached
+    pub fn next_sentence(&mut self) -> bool {
+        if self.sentence + 1 < self.lines.get(self.line).map_or(0, |l| l.len()) {
+            self.sentence += 1;
+            true
+        } else {
+            self.next_line()
+        }
+    }
+
+    pub fn previous_sentence(&mut self) {
+        if self.sentence > 0 {
+            self.sentence -= 1;
+        } else if self.line > 0 {
+            self.line -= 1;
+            self.sentence = self.lines[self.line].len() - 1;
+        }
+    }
+
+    /// Moves to the start of the next line, returning false once the end of the text is reached
+    pub fn next_line(&mut self) -> bool {
+        self.sentence = 0;
+        self.line += 1;
+        self.line < self.lines.len()
+    }
+
+    /// Moves to the start of the current line, or the previous line if already at the start
+    pub fn previous_line(&mut self) {
+        if self.sentence == 0 && self.line > 0 {
+            self.line -= 1;
+        }
+        self.sentence = 0;
+    }
+}
+
+/// Abbreviations whose full stop doesn't end a sentence, in lower case and without the last stop
+const ABBREVIATIONS: [&str; 13] = [
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "fig", "approx", "e.g", "i.e",
+];
+
+/// Splits a line after each `.`, `!` or `?` that is followed by whitespace.
+/// Decimals, abbreviations like "Dr." and initials like "J." are left in their sentence
+pub fn split_sentences(line: &str) -> Vec<String> {
+    let mut sentences = vec![];
+    let mut current = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        current.push(c);
+        if matches!(c, '.' | '!' | '?')
+            && chars.peek().is_none_or(|n| n.is_whitespace())
+            && !(c == '.' && is_abbreviation(&current))
+        {
+            sentences.push(current.trim().to_string());
+            current.clear();
+        }
+    }
+    sentences.push(current.trim().to_string());
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+/// Whether the last word of `text`, which ends in a full stop, is an abbreviation or an initial
+fn is_abbreviation(text: &str) -> bool {
+    let word = text
+        .split_whitespace()
+        .last()
+        .unwrap_or_default()
+        .trim_start_matches(|c: char| !c.is_alphanumeric())
+        .trim_end_matches('.');
+    let mut letters = word.chars();
+    let initial = letters.next().is_some_and(|c| c.is_uppercase()) && letters.next().is_none();
+    initial || ABBREVIATIONS.contains(&word.to_lowercase().as_str())
+}
+
+const NATO_ALPHABET: [&str; 26] = [
+    "Alfa", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel", "India", "Juliett",
+    "Kilo", "Lima", "Mike", "November", "Oscar", "Papa", "Quebec", "Romeo", "Sierra", "Tango",
+    "Uniform", "Victor", "Whiskey", "X-ray", "Yankee", "Zulu",
+];
+
+const DIGITS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// Spells `text` one character at a time, announcing capitals and naming symbols
+pub fn spell_out(text: &str, phonetic: bool) -> String {
+    text.chars()
+        .map(|c| spell_char(c, phonetic))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn spell_char(c: char, phonetic: bool) -> String {
+    if c.is_ascii_alphabetic() {
+        let letter = if phonetic {
+            NATO_ALPHABET[(c.to_ascii_lowercase() as u8 - b'a') as usize].to_string()
+        } else {
+            c.to_ascii_lowercase().to_string()
+        };
+        if c.is_ascii_uppercase() {
+            format!("capital {letter}")
+        } else {
+            letter
+        }
+    } else if let Some(digit) = c.to_digit(10) {
+        DIGITS[digit as usize].to_string()
+    } else if c.is_uppercase() {
+        format!("capital {c}")
+    } else {
+        match c {
+            ' ' => "space",
+            '\t' => "tab",
+            '.' => "dot",
+            ',' => "comma",
+            ':' => "colon",
+            ';' => "semicolon",
+            '-' => "dash",
+            '_' => "underscore",
+            '/' => "slash",
+            '\\' => "backslash",
+            '|' => "pipe",
+            '@' => "at",
+            '#' => "hash",
+            '$' => "dollar",
+            '%' => "percent",
+            '^' => "caret",
+            '&' => "ampersand",
+            '*' => "star",
+            '+' => "plus",
+            '=' => "equals",
+            '~' => "tilde",
+            '`' => "backtick",
+            '\'' => "apostrophe",
+            '"' => "quote",
+            '!' => "exclamation mark",
+            '?' => "question mark",
+            '(' => "open bracket",
+            ')' => "close bracket",
+            '[' => "open square bracket",
+            ']' => "close square bracket",
+            '{' => "open brace",
+            '}' => "close brace",
+            '<' => "less than",
+            '>' => "greater than",
+            _ => return c.to_string(),
+        }
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_text_as_recognised_while_speaking_it_rewritten() {
+        let queue = SpeechQueue::with_languages(
+            vec![("if a < b then. Stop".to_string(), Some("en".to_string()))],
+            |s| format!("<speak>{}</speak>", s.replace('<', "less than")),
+        );
+        assert_eq!(queue.text(), [vec!["if a < b then.", "Stop"]]);
+        assert_eq!(
+            queue.current(),
+            Some("<speak>if a less than b then.</speak>")
+        );
+        assert_eq!(queue.language(), Some("en"));
+    }
+
+    fn queue(lines: &[&str]) -> SpeechQueue {
+        SpeechQueue::with_languages(lines.iter().map(|l| (l.to_string(), None)).collect(), |s| {
+            s.to_string()
+        })
+    }
+
+    #[test]
+    fn splits_sentences_at_stops_followed_by_space() {
+        assert_eq!(
+            split_sentences("One. Two! Three? Four"),
+            ["One.", "Two!", "Three?", "Four"]
+        );
+        assert_eq!(split_sentences("Wait... what?!"), ["Wait...", "what?!"]);
+        assert_eq!(split_sentences("   "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn keeps_decimals_and_addresses_together() {
+        assert_eq!(
+            split_sentences("Pi is 3.14 and the site is example.com. Done."),
+            ["Pi is 3.14 and the site is example.com.", "Done."]
+        );
+    }
+
+    #[test]
+    fn keeps_abbreviations_and_initials_in_their_sentence() {
+        assert_eq!(
+            split_sentences("Dr. Smith met Mr. Jones, e.g. at St. Paul's. They left."),
+            ["Dr. Smith met Mr. Jones, e.g. at St. Paul's.", "They left."]
+        );
+        assert_eq!(
+            split_sentences("J. R. R. Tolkien wrote it (see fig. 2). Next."),
+            ["J. R. R. Tolkien wrote it (see fig. 2).", "Next."]
+        );
+        // A lower case single letter is a word, not an initial
+        assert_eq!(split_sentences("Plan a. Plan b."), ["Plan a.", "Plan b."]);
+    }
+
+    #[test]
+    fn steps_through_sentences_and_lines() {
+        let mut queue = queue(&["One. Two.", "Three."]);
+        assert_eq!(queue.current(), Some("One."));
+        assert!(queue.next_sentence());
+        assert_eq!(queue.current(), Some("Two."));
+        assert!(queue.next_sentence());
+        assert_eq!(queue.current(), Some("Three."));
+        assert!(!queue.next_sentence());
+        assert_eq!(queue.current(), None);
+    }
+
+    #[test]
+    fn steps_back_through_sentences_and_lines() {
+        let mut queue = queue(&["One. Two.", "Three. Four."]);
+        queue.next_line();
+        queue.previous_sentence();
+        assert_eq!(queue.current(), Some("Two."));
+
+        // Back to the start of the line, then to the line before
+        queue.next_sentence();
+        queue.next_sentence();
+        assert_eq!(queue.current(), Some("Four."));
+        queue.previous_line();
+        assert_eq!(queue.current(), Some("Three."));
+        queue.previous_line();
+        assert_eq!(queue.current(), Some("One."));
+
+        // Nothing before the start
+        queue.previous_line();
+        queue.previous_sentence();
+        assert_eq!(queue.current(), Some("One."));
+    }
+
+    #[test]
+    fn moving_keeps_the_queue_paused() {
+        let mut queue = queue(&["One. Two.", "Three."]);
+        queue.paused = true;
+        queue.next_sentence();
+        queue.next_line();
+        queue.previous_sentence();
+        assert!(queue.paused);
+        assert_eq!(queue.current(), Some("Two."));
+    }
+
+    #[test]
+    fn spells_letters_digits_and_symbols() {
+        assert_eq!(
+            spell_out("Ab1 !", false),
+            "capital a, b, one, space, exclamation mark"
+        );
+        assert_eq!(spell_out("0123456789", false), DIGITS.join(", "));
+        assert_eq!(spell_out("É€", false), "capital É, €");
+    }
+
+    #[test]
+    fn spells_with_the_nato_alphabet() {
+        assert_eq!(spell_out("Xz", true), "capital X-ray, Zulu");
+        let alphabet = ('a'..='z').collect::<String>();
+        assert_eq!(spell_out(&alphabet, true), NATO_ALPHABET.join(", "));
+    }
+
+    #[test]
+    fn skips_empty_lines() {
+        let queue = SpeechQueue::with_languages(
+            vec![
+                ("  ".to_string(), Some("fr".to_string())),
+                ("Hello".to_string(), None),
+            ],
+            |s| s.to_string(),
+        );
+        assert_eq!(queue.text(), [vec!["Hello"]]);
+        assert_eq!(queue.language(), None);
+    }
+}
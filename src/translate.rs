@@ -0,0 +1,246 @@
+// This file is part of draw-read
+// Copyright (C) 2024 agaeki
+
+use iced::futures::channel::oneshot;
+use serde_json::json;
+use serde_json::Value;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+
+/// How long to wait for the translation server before giving up and reading the original text
+const TRANSLATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Translates each of `lines` with a LibreTranslate compatible server, e.g. `http://localhost:5000/translate`.
+/// Only plain http is supported, the server is expected to be running on this machine or the local network
+pub fn translate(
+    endpoint: &str,
+    source: &str,
+    target: &str,
+    lines: &[String],
+) -> Result<Vec<String>, String> {
+    if lines.is_empty() {
+        return Ok(vec![]);
+    }
+    let body = json!({
+        "q": lines,
+        "source": source,
+        "target": target,
+        "format": "text",
+    })
+    .to_string();
+    let response = post(endpoint, &body)?;
+    let response: Value = serde_json::from_slice(&response)
+        .map_err(|e| format!("Unexpected response from {endpoint}: {e}"))?;
+
+    match &response["translatedText"] {
+        Value::Array(translated) => Ok(translated
+            .iter()
+            .map(|line| line.as_str().unwrap_or_default().to_string())
+            .collect()),
+        // Older servers only take a single piece of text
+        Value::String(translated) => Ok(translated.lines().map(|l| l.to_string()).collect()),
+        _ => Err(match response["error"].as_str() {
+            Some(error) => format!("Translation failed: {error}"),
+            None => format!("Unexpected response from {endpoint}: {response}"),
+        }),
+    }
+}
+
+/// Runs [`translate`] on its own thread, so waiting for the server doesn't hold up the app
+pub async fn translate_in_background(
+    endpoint: String,
+    source: String,
+    target: String,
+    lines: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(translate(&endpoint, &source, &target, &lines));
+    });
+    receiver
+        .await
+        .unwrap_or_else(|_| Err("Translation stopped unexpectedly".to_string()))
+}
+
+/// Sends `body` as JSON to an `http://host[:port]/path` URL, returning the response body
+fn post(endpoint: &str, body: &str) -> Result<Vec<u8>, String> {
+    let address = endpoint
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Translation endpoint must start with http:// ({endpoint})"))?;
+    let (host, path) = match address.find('/') {
+        Some(i) => (&address[..i], &address[i..]),
+        None => (address, "/translate"),
+    };
+    let host_and_port = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:80")
+    };
+
+    let socket_address = host_and_port
+        .to_socket_addrs()
+        .map_err(|e| format!("{host}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("{host}: no address found"))?;
+    let mut stream = TcpStream::connect_timeout(&socket_address, TRANSLATION_TIMEOUT)
+        .map_err(|e| format!("{endpoint}: {e}"))?;
+    stream
+        .set_read_timeout(Some(TRANSLATION_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\n\
+         Accept: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .map_err(|e| format!("{endpoint}: {e}"))?;
+
+    let mut response = vec![];
+    stream
+        .read_to_end(&mut response)
+        .map_err(|e| format!("{endpoint}: {e}"))?;
+
+    let head_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| format!("{endpoint}: incomplete response"))?;
+    // Only the head is decoded here, the body may be split part way through a character until it is dechunked
+    let head = String::from_utf8_lossy(&response[..head_end]);
+    let body = &response[head_end + 4..];
+    let status = head.lines().next().unwrap_or_default();
+    if !status
+        .split_whitespace()
+        .nth(1)
+        .is_some_and(|code| code.starts_with('2'))
+    {
+        return Err(format!("{endpoint}: {status}"));
+    }
+    let chunked = head.lines().any(|header| {
+        let header = header.to_ascii_lowercase();
+        header.starts_with("transfer-encoding:") && header.contains("chunked")
+    });
+    Ok(if chunked {
+        dechunk(body)
+    } else {
+        body.to_vec()
+    })
+}
+
+/// Joins the pieces of a chunked HTTP body
+fn dechunk(body: &[u8]) -> Vec<u8> {
+    let mut joined = vec![];
+    let mut rest = body;
+    while let Some(line_end) = rest.windows(2).position(|w| w == b"\r\n") {
+        let size = String::from_utf8_lossy(&rest[..line_end]);
+        let size =
+            usize::from_str_radix(size.split(';').next().unwrap_or("0").trim(), 16).unwrap_or(0);
+        let after = &rest[line_end + 2..];
+        if size == 0 || after.len() < size {
+            break;
+        }
+        joined.extend_from_slice(&after[..size]);
+        rest = after[size..]
+            .strip_prefix(b"\r\n")
+            .unwrap_or(&after[size..]);
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::net::TcpListener;
+
+    /// Answers one request on a local port with `response`, returning the endpoint to send it to
+    fn serve(response: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(&response).unwrap();
+        });
+        format!("http://127.0.0.1:{port}/translate")
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn reads_plain_reply() {
+        let body = r#"{"translatedText": ["hola", "mundo"]}"#;
+        let endpoint = serve(
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes(),
+        );
+        assert_eq!(
+            translate(&endpoint, "en", "es", &lines(&["hello", "world"])),
+            Ok(lines(&["hola", "mundo"]))
+        );
+    }
+
+    #[test]
+    fn reads_chunked_reply_split_inside_a_character() {
+        let body = r#"{"translatedText": ["café"]}"#.as_bytes();
+        // Split between the two bytes of the é
+        let split = body.iter().position(|&b| b == 0xC3).unwrap() + 1;
+        let mut response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        for chunk in [&body[..split], &body[split..]] {
+            response.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            response.extend_from_slice(chunk);
+            response.extend_from_slice(b"\r\n");
+        }
+        response.extend_from_slice(b"0\r\n\r\n");
+
+        let endpoint = serve(response);
+        assert_eq!(
+            translate(&endpoint, "en", "fr", &lines(&["coffee"])),
+            Ok(lines(&["café"]))
+        );
+    }
+
+    #[test]
+    fn reports_error_status() {
+        let endpoint = serve(
+            b"HTTP/1.1 400 Bad Request\r\nContent-Length: 29\r\n\r\n{\"error\": \"Invalid language\"}"
+                .to_vec(),
+        );
+        let error = translate(&endpoint, "en", "xx", &lines(&["hello"])).unwrap_err();
+        assert!(error.contains("400"), "{error}");
+    }
+
+    #[test]
+    fn reports_refused_connection() {
+        // Nothing is listening once the listener is dropped
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let endpoint = format!("http://127.0.0.1:{port}/translate");
+        assert!(translate(&endpoint, "en", "es", &lines(&["hello"])).is_err());
+    }
+}